@@ -0,0 +1,242 @@
+use container::ContainerBuilder;
+use errors::ConfigError;
+use methods::Dependency;
+use reflect;
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Map, Value};
+use toml;
+
+use std::any::{self, Any};
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+
+/// The field of a service entry naming its implementation.
+const IMPL: &str = "impl";
+
+// ++++++++++++++++++++ ConfigRegistry ++++++++++++++++++++
+
+enum CtorError {
+    Params(serde_json::Error),
+    Creation(Box<dyn StdError + Send + Sync>),
+}
+
+/// A constructed service with its type name and declared dependencies.
+type Constructed<Key, SvcBase> = (Box<SvcBase>, &'static str, Vec<Dependency<Key>>);
+
+type ConfigCtor<Key, SvcBase> = Box<
+    dyn Fn(Value) -> Result<Constructed<Key, SvcBase>, CtorError> + Send + Sync
+>;
+
+/// Named constructors, which `ContainerBuilder::from_config` chooses from.
+pub struct ConfigRegistry<Key: 'static, SvcBase: ?Sized> {
+    ctors: BTreeMap<String, ConfigCtor<Key, SvcBase>>,
+}
+
+impl<Key, SvcBase: ?Sized> ConfigRegistry<Key, SvcBase>
+    where Key: reflect::Key, SvcBase: Any
+{
+    pub fn new() -> Self {
+        ConfigRegistry{ ctors: BTreeMap::new() }
+    }
+
+    /// Adds the constructor `name`, replacing a previous one of the same name.
+    ///
+    /// The parameters `P` get deserialized from the entry of the service in the
+    /// config document, without its `impl` field. The service is registered with
+    /// the dependencies `Svc` declares, see `reflect::Service::dependencies`.
+    pub fn add<Svc, P, E>(
+        &mut self,
+        name: &str,
+        ctor: impl Fn(P) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        P: DeserializeOwned,
+        E: StdError + Send + Sync + 'static,
+    {
+        self.ctors.insert(name.to_owned(), Box::new(move |params| {
+            let params = params_from(params).map_err(CtorError::Params)?;
+            match ctor(params) {
+                Ok(svc) => {
+                    let mut deps = Vec::new();
+                    Svc::dependencies(&mut deps);
+                    Ok((svc.into(), any::type_name::<Svc>(), deps))
+                }
+                Err(err) => Err(CtorError::Creation(Box::new(err))),
+            }
+        }));
+        self
+    }
+
+    /// Returns the names of all constructors.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.ctors.keys().map(|name| &name[..])
+    }
+}
+
+impl<Key, SvcBase: ?Sized> Default for ConfigRegistry<Key, SvcBase>
+    where Key: reflect::Key, SvcBase: Any
+{
+    fn default() -> Self { Self::new() }
+}
+
+/// Deserializes `params`, an object. Parameter-less constructors may also take `()`.
+fn params_from<P: DeserializeOwned>(params: Value) -> Result<P, serde_json::Error> {
+    let empty = params.as_object().is_some_and(Map::is_empty);
+    match serde_json::from_value(params) {
+        Err(_) if empty => serde_json::from_value(Value::Null),
+        res => res,
+    }
+}
+
+// ++++++++++++++++++++ ConfigDoc ++++++++++++++++++++
+
+/// A config document choosing the implementation of each service, e.g.:
+///
+/// ```toml
+/// [db]
+/// impl = "postgres"
+/// url = "postgres://localhost/app"
+///
+/// [cache]
+/// impl = "memory"
+/// ```
+///
+/// Every top-level entry is a service key, its `impl` field names a constructor of
+/// the `ConfigRegistry` and the remaining fields are its parameters.
+/// Services are registered in the order of the document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigDoc {
+    services: Map<String, Value>,
+}
+
+impl ConfigDoc {
+    pub fn from_json(doc: &str) -> Result<Self, ConfigError> {
+        let value = serde_json::from_str(doc).map_err(|err| ConfigError::Parse{ error: Box::new(err) })?;
+        Self::from_value(value)
+    }
+
+    pub fn from_toml(doc: &str) -> Result<Self, ConfigError> {
+        let value = toml::from_str(doc).map_err(|err| ConfigError::Parse{ error: Box::new(err) })?;
+        Self::from_value(value)
+    }
+
+    /// Uses an already parsed document, which has to be an object.
+    pub fn from_value(value: Value) -> Result<Self, ConfigError> {
+        match value {
+            Value::Object(services) => Ok(ConfigDoc{ services }),
+            _ => Err(ConfigError::Parse{ error: "config document is not a table".into() }),
+        }
+    }
+}
+
+// ++++++++++++++++++++ ContainerBuilder ++++++++++++++++++++
+
+impl<Key, SvcBase: ?Sized> ContainerBuilder<Key, SvcBase>
+    where Key: reflect::Key + DeserializeOwned, SvcBase: Any
+{
+    /// Creates a builder with the services of `doc` registered, each constructed by
+    /// the constructor of `registry` it names, see `ConfigDoc`.
+    ///
+    /// Keys are deserialized from the strings of the document.
+    pub fn from_config(registry: &ConfigRegistry<Key, SvcBase>, doc: &ConfigDoc) -> Result<Self, ConfigError> {
+        let mut builder = ContainerBuilder::new();
+        for (name, entry) in &doc.services {
+            let mut params = match *entry {
+                Value::Object(ref params) => params.clone(),
+                _ => return Err(ConfigError::NotATable{ key: name.clone() }),
+            };
+            let imp = match params.remove(IMPL) {
+                Some(Value::String(imp)) => imp,
+                _ => return Err(ConfigError::MissingImpl{ key: name.clone() }),
+            };
+            let ctor = match registry.ctors.get(&imp) {
+                Some(ctor) => ctor,
+                None => return Err(ConfigError::UnknownImpl{ key: name.clone(), name: imp }),
+            };
+            let key = serde_json::from_value::<Key>(Value::String(name.clone()))
+                .map_err(|err| ConfigError::InvalidKey{ key: name.clone(), error: Box::new(err) })?;
+            let (svc, type_name, deps) = match ctor(Value::Object(params)) {
+                Ok(svc) => svc,
+                Err(CtorError::Params(err)) => {
+                    return Err(ConfigError::InvalidParams{ key: name.clone(), name: imp, error: Box::new(err) });
+                }
+                Err(CtorError::Creation(err)) => {
+                    return Err(ConfigError::CreationError{ key: name.clone(), name: imp, error: err });
+                }
+            };
+            builder.register_boxed(key, svc, type_name, deps);
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::DummyError;
+    use methods::{Access, Dependencies, Read};
+    use testing::{Base, BoxedBase};
+
+    use std::sync::OnceLock;
+
+    struct Db;
+    struct Cache;
+
+    impl reflect::Service for Db {
+        type Key = String;
+
+        fn key() -> &'static String {
+            static KEY: OnceLock<String> = OnceLock::new();
+            KEY.get_or_init(|| "db".to_owned())
+        }
+    }
+
+    impl reflect::Service for Cache {
+        type Key = String;
+
+        fn key() -> &'static String {
+            static KEY: OnceLock<String> = OnceLock::new();
+            KEY.get_or_init(|| "cache".to_owned())
+        }
+
+        fn dependencies(out: &mut Vec<Dependency<String>>) {
+            <Read<Db> as Dependencies<String>>::dependencies(out)
+        }
+    }
+
+    impl From<Db> for BoxedBase {
+        fn from(svc: Db) -> Self { Box::new(svc) }
+    }
+
+    impl From<Cache> for BoxedBase {
+        fn from(svc: Cache) -> Self { Box::new(svc) }
+    }
+
+    fn registry() -> ConfigRegistry<String, dyn Base> {
+        let mut registry = ConfigRegistry::new();
+        registry.add::<Db, (), DummyError>("postgres", |()| Ok(Db));
+        registry.add::<Cache, (), DummyError>("memory", |()| Ok(Cache));
+        registry
+    }
+
+    #[test]
+    fn config_services_declare_dependencies() {
+        let doc = ConfigDoc::from_toml("[cache]\nimpl = \"memory\"\n[db]\nimpl = \"postgres\"\n").unwrap();
+        let ioc = ContainerBuilder::from_config(&registry(), &doc).unwrap().try_build().unwrap();
+        let deps = ioc.dependencies(&"cache".to_owned()).unwrap();
+        assert_eq!(deps, [Dependency{ key: <Db as reflect::Service>::key(), access: Access::Read }]);
+
+        let doc = ConfigDoc::from_toml("[cache]\nimpl = \"memory\"\n").unwrap();
+        assert!(ContainerBuilder::from_config(&registry(), &doc).unwrap().try_build().is_err());
+    }
+
+    #[test]
+    fn config_docs_keep_their_order() {
+        let doc = ConfigDoc::from_toml("[db]\nimpl = \"postgres\"\n[cache]\nimpl = \"memory\"\n").unwrap();
+        assert!(doc.services.keys().eq(["db", "cache"]));
+        let doc = ConfigDoc::from_json(r#"{"db": {"impl": "postgres"}, "cache": {"impl": "memory"}}"#).unwrap();
+        assert!(doc.services.keys().eq(["db", "cache"]));
+    }
+}
@@ -1,263 +1,2945 @@
+use diagnostics::{LockHolder, LockReport};
 use errors::{self, Error};
-use methods::Method;
+use factory::FactoryBase;
+use graph::{DependencyGraph, Edge, Node};
+use lifecycle::{Dispose, Lifecycle, Phase};
+use methods::{Access, Dependencies, Dependency, Method};
+use observer::Observer;
 use reflect;
+use stats::{ContainerStats, LockStats};
 
 use downcast::{self, Downcast};
 
 use std::any::Any;
-use std::collections::BTreeMap;
-use std::sync::{TryLockError, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::cell::Cell;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error as StdError;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock, TryLockError, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
 
-fn type_name<T: Any>() -> &'static str {
-    "NOT IMPLEMENTED"
-    //unsafe { ::std::intrinsics::type_name::<T>() }
+fn type_name<T: ?Sized + Any>() -> &'static str {
+    ::std::any::type_name::<T>()
 }
 
-// ++++++++++++++++++++ Container ++++++++++++++++++++
+// ++++++++++++++++++++ Entry ++++++++++++++++++++
 
-pub type ReadGuard<'a, T, Base: ?Sized> = downcast::Guard<T, RwLockReadGuard<'a, Box<Base>>>;
-pub type WriteGuard<'a, T, Base: ?Sized> = downcast::Guard<T, RwLockWriteGuard<'a, Box<Base>>>;
+/// How long a service instance lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lifetime {
+    /// One instance per container, handed out behind a `RwLock`.
+    Singleton,
+    /// A new instance for every resolve, handed out by value.
+    Transient,
+    /// One instance per `Container::scope`, handed out behind a `RwLock`.
+    Scoped,
+}
 
-pub struct Container<Key, SvcBase: ?Sized> {
-    services: BTreeMap<Key, RwLock<Box<SvcBase>>>,
-    deadlock_protection: Mutex<()>,
+/// Whether locks may be waited on (`resolve`) or not (`try_resolve`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Acquire {
+    Block,
+    Try,
 }
 
-impl<Key, SvcBase: ?Sized> Container<Key, SvcBase> 
+type Constructor<Key, SvcBase> = Arc<
+    dyn for<'a> Fn(&'a Container<Key, SvcBase>, Acquire) -> Result<Box<SvcBase>, Error<'a, Key>> + Send + Sync
+>;
+
+fn constructor<Key, SvcBase, Svc, Deps, E>(
+    ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+) -> Constructor<Key, SvcBase>
+where
+    Key: reflect::Key,
+    SvcBase: ?Sized + Any,
+    Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    Deps: for<'a> Method<'a, Key, SvcBase>,
+    E: StdError + Send + Sync + 'static,
+{
+    Arc::new(move |ioc, acquire| {
+        let deps = ioc.resolve_with::<Deps>(acquire).map_err(|err| err.resolving(Svc::key()))?;
+        match ctor(deps) {
+            Ok(svc) => Ok(svc.into()),
+            Err(err) => Err(Error::CreationError{ key: Svc::key(), error: Box::new(err) }),
+        }
+    })
+}
+
+fn method_dependencies<Key, Deps>() -> Vec<Dependency<Key>>
+    where Key: reflect::Key, Deps: Dependencies<Key>
+{
+    let mut deps = Vec::new();
+    Deps::dependencies(&mut deps);
+    deps
+}
+
+/// A singleton (or scoped instance) which gets constructed the first time its key is touched.
+struct Lazy<Key: reflect::Key, SvcBase: ?Sized + Any> {
+    instance: OnceLock<RwLock<Box<SvcBase>>>,
+    init: Mutex<()>,
+    /// The thread running `ctor`, if any.
+    constructing: Mutex<Option<ThreadId>>,
+    ctor: Constructor<Key, SvcBase>,
+    lifetime: Lifetime,
+}
+
+impl<Key, SvcBase: ?Sized> Lazy<Key, SvcBase> 
     where Key: reflect::Key, SvcBase: Any
 {
-    #[doc(hidden)]
-    pub fn new() -> Self {
-        Container{ services: BTreeMap::new(), deadlock_protection: Mutex::new(()) }
+    fn new(ctor: Constructor<Key, SvcBase>, lifetime: Lifetime) -> Self {
+        Lazy{ instance: OnceLock::new(), init: Mutex::new(()), constructing: Mutex::new(None), ctor, lifetime }
     }
 
-    #[doc(hidden)]
-    pub fn register_service(&mut self, key: Key, svc: Box<SvcBase>) -> &mut Self {
-        self.services.insert(key, RwLock::new(svc));
-        self
+    fn constructing<'a>(&'a self) -> MutexGuard<'a, Option<ThreadId>> {
+        self.constructing.lock().unwrap_or_else(PoisonError::into_inner)
     }
+}
 
-    #[doc(hidden)]
-    pub fn register<Svc>(&mut self, svc: Svc) -> &mut Self
-    where
-        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
-    {
-        self.register_service(Svc::key().clone(), svc.into())
+/// Marks the current thread as constructing a `Lazy` for as long as it lives.
+struct Constructing<'a, Key: reflect::Key + 'a, SvcBase: ?Sized + Any + 'a> {
+    lazy: &'a Lazy<Key, SvcBase>,
+}
+
+impl<'a, Key, SvcBase: ?Sized> Constructing<'a, Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    fn enter(lazy: &'a Lazy<Key, SvcBase>) -> Self {
+        *lazy.constructing() = Some(thread::current().id());
+        Constructing{ lazy }
     }
+}
 
-    #[doc(hidden)]
-    pub fn register_default<Svc>(&mut self) -> &mut Self
-    where
-        Svc: Default + reflect::Service<Key = Key> + Into<Box<SvcBase>>,
-    {
-        self.register(Svc::default())
+impl<'a, Key, SvcBase: ?Sized> Drop for Constructing<'a, Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    fn drop(&mut self) {
+        *self.lazy.constructing() = None;
     }
+}
+
+enum Instance<Key: reflect::Key, SvcBase: ?Sized + Any> {
+    Singleton(RwLock<Box<SvcBase>>),
+    Lazy(Lazy<Key, SvcBase>),
+    Transient(Constructor<Key, SvcBase>),
+    /// Only a template, every scope gets its own `Instance::Lazy` from this.
+    Scoped(Constructor<Key, SvcBase>),
+}
 
-    pub fn services(&self) -> &BTreeMap<Key, RwLock<Box<SvcBase>>> {
-        &self.services
+/// Calls a `Lifecycle` hook on a service, see `ContainerBuilder::lifecycle`.
+type LifecycleHook<SvcBase> = fn(&mut SvcBase, Phase) -> Result<(), Box<dyn StdError + Send + Sync>>;
+
+fn lifecycle_hook<Key, SvcBase, Svc>(svc: &mut SvcBase, phase: Phase) -> Result<(), Box<dyn StdError + Send + Sync>>
+where
+    Key: reflect::Key,
+    SvcBase: ?Sized + Downcast<Svc>,
+    Svc: reflect::Service<Key = Key> + Lifecycle,
+{
+    let svc = match svc.downcast_mut() {
+        Some(svc) => svc,
+        None => return Err(Box::new(Error::MismatchedType{ 
+            key: Svc::key(), 
+            expected: type_name::<Svc>(), 
+            found: type_name::<SvcBase>() 
+        }.into_owned())),
+    };
+    let res = match phase {
+        Phase::Init => svc.init(),
+        Phase::Start => svc.start(),
+        Phase::Stop => svc.stop(),
+    };
+    res.map_err(|err| Box::new(err) as Box<dyn StdError + Send + Sync>)
+}
+
+/// Calls `Dispose::dispose` on a service, see `ContainerBuilder::dispose`.
+type DisposeHook<SvcBase> = fn(&mut SvcBase);
+
+fn dispose_hook<SvcBase, Svc>(svc: &mut SvcBase)
+    where SvcBase: ?Sized + Downcast<Svc>, Svc: Dispose + Any
+{
+    if let Some(svc) = svc.downcast_mut() {
+        svc.dispose();
     }
-    
-    pub fn get_service(&self, key: &Key) -> Option<&RwLock<Box<SvcBase>>> {
-        self.services.get(key)
+}
+
+/// Lock statistics of a service, see `ContainerBuilder::collect_stats`. Durations 
+/// are in nanoseconds.
+#[derive(Default)]
+struct LockCounters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    wait: AtomicU64,
+    hold: AtomicU64,
+    max_hold: AtomicU64,
+    would_block: AtomicU64,
+    poisoned: AtomicU64,
+}
+
+impl LockCounters {
+    fn acquired<Key: reflect::Key>(&self, access: Access, wait: Duration, res: Result<(), &Error<Key>>) {
+        self.wait.fetch_add(nanos(wait), Ordering::Relaxed);
+        let counter = match res {
+            Ok(()) if access == Access::Write => &self.writes,
+            Ok(()) => &self.reads,
+            Err(&Error::WouldBlock{ .. }) => &self.would_block,
+            Err(&Error::Poisoned{ .. }) => &self.poisoned,
+            Err(_) => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn read_service_base<'a>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<RwLockReadGuard<Box<SvcBase>>, Error<'a, Key>> {
-        match self.get_service(key) {
-            Some(service) => errors::or_err(key, service.read()),
-            None => Err(Error::NotFound{ key: key })
+    fn released(&self, hold: Duration) {
+        let hold = nanos(hold);
+        self.hold.fetch_add(hold, Ordering::Relaxed);
+        self.max_hold.fetch_max(hold, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LockStats {
+        LockStats{
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            wait: Duration::from_nanos(self.wait.load(Ordering::Relaxed)),
+            hold: Duration::from_nanos(self.hold.load(Ordering::Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold.load(Ordering::Relaxed)),
+            would_block: self.would_block.load(Ordering::Relaxed),
+            poisoned: self.poisoned.load(Ordering::Relaxed),
         }
     }
+}
 
-    pub fn write_service_base<'a>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<RwLockWriteGuard<Box<SvcBase>>, Error<'a, Key>> {
-        match self.get_service(key) {
-            Some(service) => errors::or_err(key, service.write()),
-            None => Err(Error::NotFound{ key: key })
+fn nanos(duration: Duration) -> u64 {
+    cmp::min(duration.as_nanos(), u128::from(u64::MAX)) as u64
+}
+
+/// Threads holding or waiting for the lock of a service, see 
+/// `ContainerBuilder::track_holders`.
+#[derive(Default)]
+struct Holders {
+    next: AtomicU64,
+    /// Whether the thread is waiting, by id.
+    list: Mutex<BTreeMap<u64, (LockHolder, bool)>>,
+}
+
+impl Holders {
+    fn wait(&self, holder: LockHolder) -> u64 {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.list().insert(id, (holder, true));
+        id
+    }
+
+    fn acquired(&self, id: u64) {
+        if let Some(&mut (ref mut holder, ref mut waiting)) = self.list().get_mut(&id) {
+            holder.since = Instant::now();
+            *waiting = false;
         }
     }
 
-    pub fn read_service<'a, Svc>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<ReadGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: Any, SvcBase: Downcast<Svc>
-    {
-        let base = try!{self.read_service_base(key)};
-        if !base.is_type() {
-            return Err(Error::MismatchedType{ 
-                key: key, 
-                expected: type_name::<Svc>(),
-                found: type_name::<Svc>(),
-            })
-        };
-        Ok(ReadGuard::wrap(base).ok().unwrap())
+    fn remove(&self, id: u64) {
+        self.list().remove(&id);
     }
 
-    pub fn write_service<'a, Svc>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<WriteGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: Any, SvcBase: Downcast<Svc>
-    {
-        let base = try!{self.write_service_base(key)};
-        if !base.is_type() {
-            return Err(Error::MismatchedType{ 
-                key: key, 
-                expected: type_name::<Svc>(),
-                found: type_name::<Svc>(),
-            })
-        };
-        Ok(WriteGuard::wrap(base).ok().unwrap())
+    fn list<'a>(&'a self) -> MutexGuard<'a, BTreeMap<u64, (LockHolder, bool)>> {
+        self.list.lock().unwrap_or_else(PoisonError::into_inner)
     }
+}
 
-    pub fn read<'a, Svc>(
-        &'a self
-    ) -> Result<ReadGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
-    {
-        self.read_service(Svc::key())
+thread_local! {
+    /// Where the outermost call into a container on this thread happened, see 
+    /// `ContainerBuilder::track_holders`.
+    static CALLER: Cell<Option<&'static Location<'static>>> = const { Cell::new(None) };
+}
+
+/// Sets `CALLER` for as long as it lives, unless it is already set.
+struct CallerScope {
+    set: bool,
+}
+
+impl CallerScope {
+    fn enter(location: &'static Location<'static>) -> Self {
+        CALLER.with(|caller| {
+            let set = caller.get().is_none();
+            if set {
+                caller.set(Some(location));
+            }
+            CallerScope{ set }
+        })
     }
+}
 
-    pub fn write<'a, Svc>(
-        &'a self
-    ) -> Result<WriteGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
-    {
-        self.write_service(Svc::key())
+impl Drop for CallerScope {
+    fn drop(&mut self) {
+        if self.set {
+            CALLER.with(|caller| caller.set(None));
+        }
     }
+}
 
-    pub fn try_read_service_base<'a>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<RwLockReadGuard<Box<SvcBase>>, Error<'a, Key>> {
-        match self.get_service(key) {
-            Some(service) => errors::or_err(key, service.read()),
-            None => Err(Error::NotFound{ key: key })
+/// Describes the service of an `Entry`, changes with `Container::replace_service`.
+struct Meta<Key: reflect::Key, SvcBase: ?Sized> {
+    /// Name of the concrete service type, as far as it is known.
+    type_name: &'static str,
+    /// Declared dependencies, see `reflect::Service::dependencies`.
+    deps: Vec<Dependency<Key>>,
+    lifecycle: Option<LifecycleHook<SvcBase>>,
+    dispose: Option<DisposeHook<SvcBase>>,
+}
+
+impl<Key: reflect::Key, SvcBase: ?Sized> Clone for Meta<Key, SvcBase> {
+    fn clone(&self) -> Self {
+        Meta{ 
+            type_name: self.type_name, 
+            deps: self.deps.clone(), 
+            lifecycle: self.lifecycle, 
+            dispose: self.dispose,
         }
     }
+}
 
-    pub fn try_write_service_base<'a>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<RwLockWriteGuard<Box<SvcBase>>, Error<'a, Key>> {
-        match self.get_service(key) {
-            Some(service) => errors::or_err(key, service.write()),
-            None => Err(Error::NotFound{ key: key })
+struct Entry<Key: reflect::Key, SvcBase: ?Sized + Any> {
+    key: Key,
+    instance: Instance<Key, SvcBase>,
+    meta: Mutex<Meta<Key, SvcBase>>,
+    /// Registration order, scoped instances share it with their template.
+    seq: usize,
+    counters: LockCounters,
+    holders: Holders,
+    /// Whether references bound to the container have been handed out, see `Services::expose`.
+    exposed: AtomicBool,
+}
+
+impl<Key, SvcBase: ?Sized> Entry<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    /// Whether resolving the service constructs it, i.e. it is transient or a lazy 
+    /// service which hasn't been constructed yet.
+    fn is_pending(&self) -> bool {
+        match self.instance {
+            Instance::Lazy(ref lazy) => lazy.instance.get().is_none(),
+            Instance::Transient(_) => true,
+            Instance::Singleton(_) | Instance::Scoped(_) => false,
         }
     }
 
-    pub fn try_read_service<'a, Svc>(
-        &'a self, 
-        key: &'a Key
-    ) -> Result<ReadGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: Any, SvcBase: Downcast<Svc>
-    {
-        let base = try!{self.try_read_service_base(key)};
-        if !base.is_type() {
-            return Err(Error::MismatchedType{ 
-                key: key, 
-                expected: type_name::<Svc>(),
-                found: type_name::<Svc>(),
-            })
+    /// Returns the lock of the service if it has already been constructed.
+    fn get(&self) -> Option<&RwLock<Box<SvcBase>>> {
+        match self.instance {
+            Instance::Singleton(ref lock) => Some(lock),
+            Instance::Lazy(ref lazy) => lazy.instance.get(),
+            Instance::Transient(_) | Instance::Scoped(_) => None,
+        }
+    }
+
+    fn lifetime(&self) -> Lifetime {
+        match self.instance {
+            Instance::Singleton(_) => Lifetime::Singleton,
+            Instance::Lazy(ref lazy) => lazy.lifetime,
+            Instance::Transient(_) => Lifetime::Transient,
+            Instance::Scoped(_) => Lifetime::Scoped,
+        }
+    }
+
+    /// Returns the entry for the instance of a new scope, if this is a scoped service.
+    fn scoped(&self) -> Option<Self> {
+        let ctor = match self.instance {
+            Instance::Lazy(ref lazy) if lazy.lifetime == Lifetime::Scoped => &lazy.ctor,
+            Instance::Scoped(ref ctor) => ctor,
+            _ => return None,
         };
-        Ok(ReadGuard::wrap(base).ok().unwrap())
+        Some(self.with_instance(Instance::Lazy(Lazy::new(ctor.clone(), Lifetime::Scoped))))
     }
 
-    pub fn try_write_service<'a, Svc>(
+    /// Returns an entry for a new instance of the service, which gets constructed on 
+    /// its first resolve, if the service has a constructor and isn't transient. 
+    /// Templates of scoped services stay templates.
+    fn renewed(&self) -> Option<Self> {
+        let instance = match self.instance {
+            Instance::Lazy(ref lazy) => Instance::Lazy(Lazy::new(lazy.ctor.clone(), lazy.lifetime)),
+            Instance::Scoped(ref ctor) => Instance::Scoped(ctor.clone()),
+            Instance::Singleton(_) | Instance::Transient(_) => return None,
+        };
+        Some(self.with_instance(instance))
+    }
+
+    /// Returns an entry of the same service for `instance`.
+    fn with_instance(&self, instance: Instance<Key, SvcBase>) -> Self {
+        Entry{
+            key: self.key.clone(),
+            instance,
+            meta: Mutex::new(self.meta().clone()),
+            seq: self.seq,
+            counters: LockCounters::default(),
+            holders: Holders::default(),
+            exposed: AtomicBool::new(false),
+        }
+    }
+
+    fn meta(&self) -> MutexGuard<'_, Meta<Key, SvcBase>> {
+        self.meta.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.meta().type_name
+    }
+
+    fn deps(&self) -> Vec<Dependency<Key>> {
+        self.meta().deps.clone()
+    }
+
+    /// Returns the lock of the service, constructing it first if necessary.
+    fn get_or_init<'a>(
         &'a self, 
-        key: &'a Key
-    ) -> Result<WriteGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: Any, SvcBase: Downcast<Svc>
-    {
-        let base = try!{self.try_write_service_base(key)};
-        if !base.is_type() {
-            return Err(Error::MismatchedType{ 
-                key: key, 
-                expected: type_name::<Svc>(),
-                found: type_name::<Svc>(),
-            })
+        key: &'a Key,
+        ioc: &'a Container<Key, SvcBase>,
+        acquire: Acquire
+    ) -> Result<&'a RwLock<Box<SvcBase>>, Error<'a, Key>> {
+        let lazy = match self.instance {
+            Instance::Singleton(ref lock) => return Ok(lock),
+            Instance::Lazy(ref lazy) => lazy,
+            Instance::Transient(_) | Instance::Scoped(_) => return Err(Error::MismatchedLifetime{
+                key,
+                expected: Lifetime::Singleton,
+                found: self.lifetime(),
+            }),
+        };
+        if let Some(lock) = lazy.instance.get() {
+            return Ok(lock);
+        }
+
+        // Waiting for `init` would never end if the constructor requires the service 
+        // again, be it on this thread or on another one constructing a dependency.
+        if *lazy.constructing() == Some(thread::current().id()) || ioc.constructs_itself(key) {
+            return Err(Error::Cycle{ key });
+        }
+
+        // `init` guards no data, a panicking constructor must not render the service unusable.
+        let _init = match acquire {
+            Acquire::Block => lazy.init.lock().unwrap_or_else(PoisonError::into_inner),
+            Acquire::Try => match lazy.init.try_lock() {
+                Ok(init) => init,
+                Err(TryLockError::Poisoned(err)) => err.into_inner(),
+                Err(TryLockError::WouldBlock) => return Err(Error::WouldBlock{ key }),
+            },
+        };
+        if let Some(lock) = lazy.instance.get() {
+            return Ok(lock);
+        }
+        let svc = {
+            let _constructing = Constructing::enter(lazy);
+            (lazy.ctor)(ioc, acquire)?
         };
-        Ok(WriteGuard::wrap(base).ok().unwrap())
+        Ok(lazy.instance.get_or_init(|| RwLock::new(svc)))
     }
+}
 
-    pub fn try_read<'a, Svc>(
-        &'a self
-    ) -> Result<ReadGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
-    {
-        self.try_read_service(Svc::key())
+impl<Key, SvcBase: ?Sized> Drop for Entry<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    /// Calls the `Dispose` hook, if the service has been constructed.
+    fn drop(&mut self) {
+        let hook = match self.meta.get_mut().unwrap_or_else(PoisonError::into_inner).dispose {
+            Some(hook) => hook,
+            None => return,
+        };
+        let lock = match self.instance {
+            Instance::Singleton(ref mut lock) => lock,
+            Instance::Lazy(ref mut lazy) => match lazy.instance.get_mut() {
+                Some(lock) => lock,
+                None => return,
+            },
+            Instance::Transient(_) | Instance::Scoped(_) => return,
+        };
+        let svc = lock.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if thread::panicking() {
+            // The last guard may be released while unwinding, panicking again would abort.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&mut **svc)));
+        } else {
+            hook(&mut **svc);
+        }
     }
+}
 
-    pub fn try_write<'a, Svc>(
-        &'a self
-    ) -> Result<WriteGuard<Svc, SvcBase>, Error<'a, Key>>
-        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
-    {
-        self.try_write_service(Svc::key())
+/// An `Entry` with its types erased, kept alive by the guards of its service.
+trait Retained {}
+
+impl<Key, SvcBase: ?Sized> Retained for Entry<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{}
+
+// ++++++++++++++++++++ Container ++++++++++++++++++++
+
+/// Orders `keys` such that dependencies come before their dependents, keys without 
+/// dependencies between them keep their order. Keys for which `deps` returns `None` 
+/// are left out.
+fn dependency_order<'a, Key>(
+    keys: impl IntoIterator<Item = &'a Key>,
+    deps: &dyn Fn(&Key) -> Option<&'a [Dependency<Key>]>
+) -> (Vec<&'a Key>, Vec<Vec<Key>>)
+    where Key: reflect::Key
+{
+    let mut order = Vec::new();
+    let mut cycles = Vec::new();
+    let mut done = BTreeSet::new();
+    let mut stack = Vec::new();
+    for key in keys {
+        visit(key, deps, &mut stack, &mut done, &mut order, &mut cycles);
     }
+    (order, cycles)
+}
 
-    pub fn resolve<'a, M>(&'a self) -> Result<M::Ret, Error<Key>>
-        where M: Method<'a, Key, SvcBase>
-    {
-        let _ = self.deadlock_protection.lock();
-        M::resolve_unprotected(self)
+fn visit<'a, Key>(
+    key: &'a Key, 
+    deps: &dyn Fn(&Key) -> Option<&'a [Dependency<Key>]>,
+    stack: &mut Vec<&'a Key>, 
+    done: &mut BTreeSet<&'a Key>, 
+    order: &mut Vec<&'a Key>, 
+    cycles: &mut Vec<Vec<Key>>
+)
+    where Key: reflect::Key
+{
+    if done.contains(key) {
+        return;
     }
-    pub fn try_resolve<'a, M>(&'a self) -> Result<M::Ret, Error<Key>>
-        where M: Method<'a, Key, SvcBase>
-    {
-        let _ = self.deadlock_protection.lock();
-        M::try_resolve_unprotected(self)
+    if let Some(pos) = stack.iter().position(|k| *k == key) {
+        let mut cycle: Vec<Key> = stack[pos..].iter().map(|k| (*k).clone()).collect();
+        cycle.push(key.clone());
+        cycles.push(cycle);
+        return;
+    }
+    let key_deps = match deps(key) {
+        Some(key_deps) => key_deps,
+        None => return,
+    };
+    stack.push(key);
+    for dep in key_deps {
+        visit(dep.key, deps, stack, done, order, cycles);
     }
+    stack.pop();
+    done.insert(key);
+    order.push(key);
 }
 
-// ++++++++++++++++++++ ContainerBuilder ++++++++++++++++++++
+/// Bookkeeping for a held guard, done once it is released.
+struct Held<'a, Key: reflect::Key + 'a> {
+    key: &'a Key,
+    access: Access,
+    since: Instant,
+    counters: Option<&'a LockCounters>,
+    observer: Option<&'a dyn Observer<Key>>,
+    holder: Option<(&'a Holders, u64)>,
+    /// Closed together with the guard, so its duration is the time the guard was held.
+    #[cfg(feature = "tracing")]
+    _span: ::tracing::Span,
+}
 
-pub struct ContainerBuilder<Key, SvcBase: ?Sized> {
-    cont: Container<Key, SvcBase>
+impl<'a, Key> Drop for Held<'a, Key>
+    where Key: reflect::Key
+{
+    fn drop(&mut self) {
+        let held = self.since.elapsed();
+        if let Some(counters) = self.counters {
+            counters.released(held);
+        }
+        if let Some(observer) = self.observer {
+            observer.guard_released(self.key, self.access, held);
+        }
+        if let Some((holders, id)) = self.holder {
+            holders.remove(id);
+        }
+    }
 }
 
-impl<Key, SvcBase: ?Sized> ContainerBuilder<Key, SvcBase>
+/// A `Held` with its key type erased, so guards don't depend on it.
+trait Release {}
+
+impl<'a, Key> Release for Held<'a, Key>
+    where Key: reflect::Key
+{}
+
+/// Read-guard of a service, see `Container::read_service_base`.
+pub struct BaseReadGuard<'a, SvcBase: ?Sized + 'a> {
+    guard: RwLockReadGuard<'a, Box<SvcBase>>,
+    // dropped after `guard`
+    _held: Option<Box<dyn Release + 'a>>,
+    // dropped last, `guard` and `_held` borrow from it
+    _entry: Arc<dyn Retained>,
+}
+
+impl<'a, SvcBase: ?Sized> Deref for BaseReadGuard<'a, SvcBase> {
+    type Target = Box<SvcBase>;
+    fn deref(&self) -> &Box<SvcBase> { &self.guard }
+}
+
+/// Write-guard of a service, see `Container::write_service_base`.
+pub struct BaseWriteGuard<'a, SvcBase: ?Sized + 'a> {
+    guard: RwLockWriteGuard<'a, Box<SvcBase>>,
+    // dropped after `guard`
+    _held: Option<Box<dyn Release + 'a>>,
+    // dropped last, `guard` and `_held` borrow from it
+    _entry: Arc<dyn Retained>,
+}
+
+impl<'a, SvcBase: ?Sized> Deref for BaseWriteGuard<'a, SvcBase> {
+    type Target = Box<SvcBase>;
+    fn deref(&self) -> &Box<SvcBase> { &self.guard }
+}
+
+impl<'a, SvcBase: ?Sized> DerefMut for BaseWriteGuard<'a, SvcBase> {
+    fn deref_mut(&mut self) -> &mut Box<SvcBase> { &mut self.guard }
+}
+
+pub type ReadGuard<'a, T, Base> = downcast::Guard<T, BaseReadGuard<'a, Base>>;
+pub type WriteGuard<'a, T, Base> = downcast::Guard<T, BaseWriteGuard<'a, Base>>;
+
+/// The lock of a constructed service, see `Container::service_lock`.
+struct ServiceLock<'a, Key: reflect::Key + 'a, SvcBase: ?Sized + Any + 'a> {
+    /// Borrowed from `retained`.
+    entry: &'a Entry<Key, SvcBase>,
+    lock: &'a RwLock<Box<SvcBase>>,
+    /// Those of the container owning `entry`.
+    services: &'a Services<Key, SvcBase>,
+    retained: Arc<Entry<Key, SvcBase>>,
+}
+
+impl<'a, Key, SvcBase: ?Sized> ServiceLock<'a, Key, SvcBase> 
     where Key: reflect::Key, SvcBase: Any
 {
-    pub fn new() -> Self {
-        ContainerBuilder{ cont: Container::new() }
+    /// Constructs the service of `retained` if necessary.
+    fn new(
+        key: &'a Key,
+        retained: Arc<Entry<Key, SvcBase>>, 
+        owner: &'a Container<Key, SvcBase>, 
+        acquire: Acquire
+    ) -> Result<Self, Error<'a, Key>> {
+        // SAFETY: The entry lives as long as `retained`, which is kept by `self` and 
+        // cloned into every guard borrowing from `entry`.
+        let entry: &'a Entry<Key, SvcBase> = unsafe { &*Arc::as_ptr(&retained) };
+        Ok(ServiceLock{ 
+            entry, 
+            lock: entry.get_or_init(key, owner, acquire)?, 
+            services: &owner.services, 
+            retained,
+        })
     }
 
-    pub fn register_service(&mut self, key: Key, svc: Box<SvcBase>) -> &mut Self {
-        self.cont.register_service(key, svc);
-        self
+    fn read(&self, key: &'a Key, acquire: Acquire) -> Result<BaseReadGuard<'a, SvcBase>, Error<'a, Key>> {
+        let start = self.start();
+        let waiter = self.wait(Access::Read);
+        let res = match acquire {
+            Acquire::Block => errors::or_err(key, self.lock.read()),
+            Acquire::Try => errors::or_err(key, self.lock.try_read()),
+        };
+        let held = self.acquired(key, Access::Read, start, waiter, res.as_ref().map(|_| ()));
+        res.map(|guard| BaseReadGuard{ guard, _held: held, _entry: self.retained.clone() })
     }
 
-    /// NOTE: The `Box<Svc>: Into<Box<Base>>`-clause is needed due to rusts lack of 
-    /// HKT or a `Coercible`-trait (to name two solutions).
-    pub fn register<Svc>(&mut self, svc: Svc) -> &mut Self
-    where
-        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
-    {
-        self.cont.register::<Svc>(svc);
-        self
+    fn write(&self, key: &'a Key, acquire: Acquire) -> Result<BaseWriteGuard<'a, SvcBase>, Error<'a, Key>> {
+        let start = self.start();
+        let waiter = self.wait(Access::Write);
+        let res = match acquire {
+            Acquire::Block => errors::or_err(key, self.lock.write()),
+            Acquire::Try => errors::or_err(key, self.lock.try_write()),
+        };
+        let held = self.acquired(key, Access::Write, start, waiter, res.as_ref().map(|_| ()));
+        res.map(|guard| BaseWriteGuard{ guard, _held: held, _entry: self.retained.clone() })
     }
 
-    pub fn register_default<Svc>(&mut self) -> &mut Self
-    where
-        Svc: Default + reflect::Service<Key = Key> + Into<Box<SvcBase>>,
-    {
-        self.cont.register_default::<Svc>();
-        self
+    /// Returns when locking started, if anyone is interested.
+    fn start(&self) -> Option<Instant> {
+        let services = self.services;
+        if services.stats || services.observer.is_some() || services.holders || traced() {
+            Some(Instant::now())
+        } else {
+            None
+        }
     }
 
-    pub fn build(self) -> Container<Key, SvcBase> {
-        self.cont
+    /// Registers the current thread as waiting for the lock, if holders are tracked.
+    fn wait(&self, access: Access) -> Option<u64> {
+        if !self.services.holders {
+            return None;
+        }
+        let location = CALLER.with(Cell::get);
+        Some(self.entry.holders.wait(LockHolder::current(access, location)))
+    }
+
+    fn acquired(
+        &self, 
+        key: &'a Key, 
+        access: Access, 
+        start: Option<Instant>, 
+        waiter: Option<u64>,
+        res: Result<(), &Error<'a, Key>>
+    ) -> Option<Box<dyn Release + 'a>> {
+        let start = start?;
+        let since = Instant::now();
+        let counters = if self.services.stats { Some(&self.entry.counters) } else { None };
+        if let Some(counters) = counters {
+            counters.acquired(access, since - start, res);
+        }
+        let holder = waiter.map(|id| (&self.entry.holders, id));
+        if let Some((holders, id)) = holder {
+            match res {
+                Ok(()) => holders.acquired(id),
+                Err(_) => holders.remove(id),
+            }
+        }
+        res.ok()?;
+        let observer = self.services.observer.as_deref();
+        if let Some(observer) = observer {
+            observer.lock_acquired(key, access, since - start);
+        }
+        #[cfg(feature = "tracing")]
+        let span = {
+            debug!(key = ?key, access = ?access, waited = ?(since - start), "lock acquired");
+            let span = debug_span!("guard", key = ?key, access = ?access);
+            if counters.is_none() && observer.is_none() && holder.is_none() && span.is_disabled() {
+                return None;
+            }
+            span
+        };
+        Some(Box::new(Held{ 
+            key, 
+            access, 
+            since, 
+            counters, 
+            observer, 
+            holder,
+            #[cfg(feature = "tracing")]
+            _span: span,
+        }))
     }
 }
 
-impl<Key, SvcBase: ?Sized> Default for ContainerBuilder<Key, SvcBase> 
+/// Whether a tracing subscriber is interested in the events and spans of guards.
+#[cfg(feature = "tracing")]
+fn traced() -> bool {
+    enabled!(::tracing::Level::DEBUG)
+}
+
+#[cfg(not(feature = "tracing"))]
+fn traced() -> bool {
+    false
+}
+
+fn mismatched_type<'a, Key, Svc: Any>(key: &'a Key, found: &'static str) -> Error<'a, Key> {
+    Error::MismatchedType{ 
+        key, 
+        expected: type_name::<Svc>(),
+        found,
+    }
+}
+
+/// Fails if a key is locked more than once with at least one of them being a write-lock, 
+/// which would deadlock.
+fn check_conflicts<Key>(deps: &[Dependency<Key>]) -> Result<(), Error<'static, Key>>
+    where Key: reflect::Key
+{
+    let mut locks: Vec<_> = deps.iter().filter(|dep| dep.access.is_lock()).collect();
+    locks.sort_by_key(|dep| dep.key);
+    for pair in locks.windows(2) {
+        if pair[0].key == pair[1].key && (pair[0].access == Access::Write || pair[1].access == Access::Write) {
+            return Err(Error::ConflictingAccess{ key: pair[0].key });
+        }
+    }
+    Ok(())
+}
+
+enum BaseGuard<'a, SvcBase: ?Sized + 'a> {
+    Read(BaseReadGuard<'a, SvcBase>),
+    Write(BaseWriteGuard<'a, SvcBase>),
+}
+
+/// The guards acquired for a `Method`, see `Method::finish`.
+pub struct Guards<'a, Key: 'a, SvcBase: ?Sized + 'a> {
+    inner: ::std::vec::IntoIter<(&'a Key, &'static str, BaseGuard<'a, SvcBase>)>,
+}
+
+impl<'a, Key, SvcBase: ?Sized> Guards<'a, Key, SvcBase> 
     where Key: reflect::Key, SvcBase: Any
 {
-    fn default() -> Self { Self::new() }
+    /// Takes the next guard, which has to belong to an `Access::Read` dependency.
+    ///
+    /// Panics if the next dependency isn't `Access::Read`.
+    pub fn read<Svc>(&mut self) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        match self.inner.next() {
+            Some((key, found, BaseGuard::Read(base))) => {
+                ReadGuard::wrap(base).map_err(|_| mismatched_type::<_, Svc>(key, found))
+            }
+            _ => panic!("guards have to be taken in the order of `Dependencies::dependencies`"),
+        }
+    }
+
+    /// Takes the next guard, which has to belong to an `Access::Write` dependency.
+    ///
+    /// Panics if the next dependency isn't `Access::Write`.
+    pub fn write<Svc>(&mut self) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        match self.inner.next() {
+            Some((key, found, BaseGuard::Write(base))) => {
+                WriteGuard::wrap(base).map_err(|_| mismatched_type::<_, Svc>(key, found))
+            }
+            _ => panic!("guards have to be taken in the order of `Dependencies::dependencies`"),
+        }
+    }
+}
+
+type Entries<Key, SvcBase> = BTreeMap<Key, Arc<Entry<Key, SvcBase>>>;
+
+struct Services<Key: reflect::Key, SvcBase: ?Sized + Any> {
+    /// Guards keep their entry alive, so a removed entry is dropped with its last guard.
+    entries: RwLock<Entries<Key, SvcBase>>,
+    /// Exposed entries removed through `&self`, references to them may still be alive 
+    /// until `Container::purge_removed`.
+    retired: Mutex<Vec<Arc<Entry<Key, SvcBase>>>>,
+    parent: Option<Container<Key, SvcBase>>,
+    /// Next `Entry::seq`.
+    registered: AtomicUsize,
+    /// See `ContainerBuilder::collect_stats`, inherited from the parent.
+    stats: bool,
+    /// See `ContainerBuilder::observe`, inherited from the parent.
+    observer: Option<Arc<dyn Observer<Key>>>,
+    /// See `ContainerBuilder::track_holders`, inherited from the parent.
+    holders: bool,
+}
+
+impl<Key, SvcBase: ?Sized> Services<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    fn get(&self, key: &Key) -> Option<Arc<Entry<Key, SvcBase>>> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        entries.get(key).cloned()
+    }
+
+    /// Returns the entry of `key` for handing out references bound to `self`, which 
+    /// keeps the entry until `self` is dropped or purged, see `retire`.
+    fn expose(&self, key: &Key) -> Option<&Entry<Key, SvcBase>> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        let entry = entries.get(key)?;
+        // Removing the entry requires the write-lock, so it sees this.
+        entry.exposed.store(true, Ordering::Relaxed);
+        // SAFETY: Exposed entries are only dropped together with `self` or through 
+        // `&mut self`, entries removed through `&self` are retired instead.
+        Some(unsafe { &*Arc::as_ptr(entry) })
+    }
+
+    /// Returns a snapshot of all entries.
+    fn entries(&self) -> Vec<Arc<Entry<Key, SvcBase>>> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        entries.values().cloned().collect()
+    }
+
+    fn entries_mut(&mut self) -> &mut Entries<Key, SvcBase> {
+        self.entries.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Inserts `entry` through `&self`, retiring the entry it replaces.
+    fn insert(&self, entry: Arc<Entry<Key, SvcBase>>) {
+        let old = {
+            let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+            entries.insert(entry.key.clone(), entry)
+        };
+        if let Some(old) = old {
+            self.retire(old);
+        }
+    }
+
+    /// Removes the entry of `key` through `&self` by retiring it.
+    fn remove(&self, key: &Key) -> bool {
+        let old = {
+            let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+            entries.remove(key)
+        };
+        match old {
+            Some(old) => {
+                self.retire(old);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keeps a removed entry if it is exposed, otherwise it is dropped, and disposed, 
+    /// together with its last guard.
+    fn retire(&self, entry: Arc<Entry<Key, SvcBase>>) {
+        if entry.exposed.load(Ordering::Relaxed) {
+            self.retired.lock().unwrap_or_else(PoisonError::into_inner).push(entry);
+        }
+    }
+}
+
+impl<Key, SvcBase: ?Sized> Drop for Services<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    /// Disposes the services in reverse dependency order, see `Dispose`. Removed 
+    /// services are disposed first, most recently removed first.
+    ///
+    /// A panicking service doesn't stop the others from being disposed, the first 
+    /// panic is resumed afterwards.
+    fn drop(&mut self) {
+        let mut disposed: Vec<Arc<Entry<Key, SvcBase>>> = Vec::new();
+        let retired = self.retired.get_mut().unwrap_or_else(PoisonError::into_inner);
+        disposed.extend(retired.drain(..).rev());
+
+        let entries = self.entries.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let deps: BTreeMap<&Key, _> = entries.iter().map(|(key, entry)| (key, entry.deps())).collect();
+        let mut keys: Vec<&Key> = entries.keys().collect();
+        keys.sort_by_key(|key| entries[*key].seq);
+        let (order, _) = dependency_order(keys, &|key| deps.get(key).map(|deps| &deps[..]));
+        let order: Vec<Key> = order.into_iter().rev().cloned().collect();
+        disposed.extend(order.iter().map(|key| entries.remove(key).expect("key of the container")));
+        dispose_entries(disposed);
+    }
+}
+
+/// Drops and thereby disposes `entries` in order, resuming the first panic once all 
+/// of them are disposed.
+fn dispose_entries<Key, SvcBase>(entries: Vec<Arc<Entry<Key, SvcBase>>>)
+    where Key: reflect::Key, SvcBase: ?Sized + Any
+{
+    let mut payload = None;
+    for entry in entries {
+        if let Err(err) = panic::catch_unwind(AssertUnwindSafe(move || drop(entry))) {
+            payload.get_or_insert(err);
+        }
+    }
+    if let Some(payload) = payload {
+        if !thread::panicking() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+pub struct Container<Key: reflect::Key, SvcBase: ?Sized + Any> {
+    services: Arc<Services<Key, SvcBase>>,
+}
+
+impl<Key, SvcBase: ?Sized> Container<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    #[doc(hidden)]
+    pub fn new() -> Self {
+        Self::with_parent(BTreeMap::new(), None)
+    }
+
+    fn with_parent(entries: Entries<Key, SvcBase>, parent: Option<Self>) -> Self {
+        let registered = entries.values().map(|entry| entry.seq + 1).max().unwrap_or(0);
+        let stats = parent.as_ref().is_some_and(|parent| parent.services.stats);
+        let observer = parent.as_ref().and_then(|parent| parent.services.observer.clone());
+        let holders = parent.as_ref().is_some_and(|parent| parent.services.holders);
+        Container{ services: Arc::new(Services{ 
+            entries: RwLock::new(entries), 
+            retired: Mutex::new(Vec::new()), 
+            parent, 
+            registered: AtomicUsize::new(registered),
+            stats,
+            observer,
+            holders,
+        }) }
+    }
+
+    /// Panics if scopes of this container are still alive.
+    fn services_mut(&mut self) -> &mut Services<Key, SvcBase> {
+        match Arc::get_mut(&mut self.services) {
+            Some(services) => services,
+            None => panic!("services can't be registered while scopes of the container are alive"),
+        }
+    }
+
+    fn entry(
+        &self, 
+        key: Key, 
+        instance: Instance<Key, SvcBase>, 
+        type_name: &'static str, 
+        deps: Vec<Dependency<Key>>
+    ) -> Entry<Key, SvcBase> {
+        Entry{ 
+            key,
+            instance, 
+            meta: Mutex::new(Meta{ type_name, deps, lifecycle: None, dispose: None }),
+            seq: self.services.registered.fetch_add(1, Ordering::Relaxed),
+            counters: LockCounters::default(),
+            holders: Holders::default(),
+            exposed: AtomicBool::new(false),
+        }
+    }
+
+    fn register_entry(
+        &mut self, 
+        key: Key, 
+        instance: Instance<Key, SvcBase>, 
+        type_name: &'static str, 
+        deps: Vec<Dependency<Key>>
+    ) -> &mut Self {
+        let entry = self.entry(key.clone(), instance, type_name, deps);
+        self.services_mut().entries_mut().insert(key, Arc::new(entry));
+        self
+    }
+
+    /// As the concrete type of `svc` is unknown here, errors will report it as `SvcBase`.
+    #[doc(hidden)]
+    pub fn register_service(&mut self, key: Key, svc: Box<SvcBase>) -> &mut Self {
+        self.register_entry(key, Instance::Singleton(RwLock::new(svc)), type_name::<SvcBase>(), Vec::new())
+    }
+
+    #[doc(hidden)]
+    pub fn register<Svc>(&mut self, svc: Svc) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    {
+        let mut deps = Vec::new();
+        Svc::dependencies(&mut deps);
+        self.register_entry(Svc::key().clone(), Instance::Singleton(RwLock::new(svc.into())), type_name::<Svc>(), deps)
+    }
+
+    #[doc(hidden)]
+    pub fn register_default<Svc>(&mut self) -> &mut Self
+    where
+        Svc: Default + reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    {
+        self.register(Svc::default())
+    }
+
+    #[doc(hidden)]
+    pub fn register_lazy<Svc, Deps, E>(
+        &mut self, 
+        ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        Deps: for<'a> Method<'a, Key, SvcBase>,
+        E: StdError + Send + Sync + 'static,
+    {
+        let ctor = constructor::<Key, SvcBase, Svc, Deps, E>(ctor);
+        let lazy = Lazy::new(ctor, Lifetime::Singleton);
+        self.register_entry(Svc::key().clone(), Instance::Lazy(lazy), type_name::<Svc>(), method_dependencies::<Key, Deps>())
+    }
+
+    #[doc(hidden)]
+    pub fn register_transient<Svc, Deps, E>(
+        &mut self, 
+        ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        Deps: for<'a> Method<'a, Key, SvcBase>,
+        E: StdError + Send + Sync + 'static,
+    {
+        let ctor = constructor::<Key, SvcBase, Svc, Deps, E>(ctor);
+        self.register_entry(Svc::key().clone(), Instance::Transient(ctor), type_name::<Svc>(), method_dependencies::<Key, Deps>())
+    }
+
+    #[doc(hidden)]
+    pub fn register_scoped<Svc, Deps, E>(
+        &mut self, 
+        ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        Deps: for<'a> Method<'a, Key, SvcBase>,
+        E: StdError + Send + Sync + 'static,
+    {
+        let ctor = constructor::<Key, SvcBase, Svc, Deps, E>(ctor);
+        self.register_entry(Svc::key().clone(), Instance::Scoped(ctor), type_name::<Svc>(), method_dependencies::<Key, Deps>())
+    }
+
+    /// Registers `svc` under `key` while the container may be in use, e.g. by other 
+    /// threads.
+    ///
+    /// A service already registered under `key` in this container is removed first, 
+    /// see `remove`. As the concrete type of `svc` is unknown here, errors will report 
+    /// it as `SvcBase`.
+    pub fn insert_service(&self, key: Key, svc: Box<SvcBase>) {
+        let entry = self.entry(key, Instance::Singleton(RwLock::new(svc)), type_name::<SvcBase>(), Vec::new());
+        self.services.insert(Arc::new(entry));
+    }
+
+    /// Registers `svc` under `Svc::key()`, see `insert_service`.
+    pub fn insert<Svc>(&self, svc: Svc)
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    {
+        let mut deps = Vec::new();
+        Svc::dependencies(&mut deps);
+        let entry = self.entry(Svc::key().clone(), Instance::Singleton(RwLock::new(svc.into())), type_name::<Svc>(), deps);
+        self.services.insert(Arc::new(entry));
+    }
+
+    /// Registers the services registered by `f` while the container may be in use, see 
+    /// `insert_service`. Unlike `insert`, this allows enabling their hooks, e.g.:
+    ///
+    /// ```ignore
+    /// ioc.insert_with(|b| { b.register(Pool::new()).dispose::<Pool>(); });
+    /// ```
+    pub fn insert_with(&self, f: impl FnOnce(&mut ContainerBuilder<Key, SvcBase>)) {
+        let mut inserted = ContainerBuilder::new();
+        f(&mut inserted);
+        let mut entries: Vec<_> = mem::take(inserted.cont.services_mut().entries_mut()).into_values().collect();
+        entries.sort_by_key(|entry| entry.seq);
+        for mut entry in entries {
+            if let Some(entry) = Arc::get_mut(&mut entry) {
+                entry.seq = self.services.registered.fetch_add(1, Ordering::Relaxed);
+            }
+            self.services.insert(entry);
+        }
+    }
+
+    /// Removes the service registered under `key` from this container, its parents are 
+    /// left untouched. Returns whether a service was removed.
+    ///
+    /// Guards handed out before stay valid, the service is disposed once the last of 
+    /// them is released. Services of which `get_service` or `services` handed out a 
+    /// reference are kept until the container is dropped or `purge_removed` is called.
+    pub fn remove(&self, key: &Key) -> bool {
+        self.services.remove(key)
+    }
+
+    /// Disposes and drops the services removed or replaced by `remove`, `insert` and 
+    /// its variants which are still kept because references to them have been handed 
+    /// out, most recently removed first. 
+    ///
+    /// Returns `false` without purging anything while scopes of the container are 
+    /// alive, as they may still hold such references.
+    pub fn purge_removed(&mut self) -> bool {
+        let services = match Arc::get_mut(&mut self.services) {
+            Some(services) => services,
+            None => return false,
+        };
+        let retired = services.retired.get_mut().unwrap_or_else(PoisonError::into_inner);
+        dispose_entries(retired.drain(..).rev().collect());
+        true
+    }
+
+    /// Creates a child container. 
+    ///
+    /// Services registered with `register_scoped` get a separate instance in every 
+    /// scope, which is dropped together with the scope. Everything else is looked 
+    /// up in `self`.
+    pub fn scope(&self) -> Self {
+        let entries = self.visible_entries().into_iter()
+            .filter_map(|(_, entry)| entry.scoped())
+            .map(|entry| (entry.key.clone(), Arc::new(entry)))
+            .collect();
+        let parent = Container{ services: self.services.clone() };
+        Self::with_parent(entries, Some(parent))
+    }
+
+    /// Returns a child container in which the services registered by `f` take the 
+    /// place of those registered in `self`, e.g. test doubles:
+    ///
+    /// ```ignore
+    /// let ioc = ioc.with_overrides(|b| { b.register(FakeDb); });
+    /// ```
+    ///
+    /// Lazy and scoped services of `self` which depend on an override, directly or 
+    /// through other services, get a separate instance in the child, constructed from 
+    /// the overrides. Transient services are always constructed from the container 
+    /// they're resolved from. Everything else is shared with `self`, **including 
+    /// services registered as instances, which keep the dependencies they've been 
+    /// constructed with**.
+    pub fn with_overrides(&self, f: impl FnOnce(&mut ContainerBuilder<Key, SvcBase>)) -> Self {
+        let mut overrides = ContainerBuilder::new();
+        f(&mut overrides);
+        let mut entries = mem::take(overrides.cont.services_mut().entries_mut());
+
+        let inherited: Vec<_> = self.visible_entries().into_iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| !entries.contains_key(&entry.key))
+            .collect();
+
+        let mut affected: BTreeSet<Key> = entries.keys().cloned().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for entry in &inherited {
+                if affected.contains(&entry.key) || !entry.meta().deps.iter().any(|dep| affected.contains(dep.key)) {
+                    continue;
+                }
+                affected.insert(entry.key.clone());
+                changed = true;
+                if let Some(renewed) = entry.renewed() {
+                    entries.insert(entry.key.clone(), Arc::new(renewed));
+                }
+            }
+        }
+
+        let parent = Container{ services: self.services.clone() };
+        Self::with_parent(entries, Some(parent))
+    }
+
+    /// Returns the entry registered under `key` together with the container (self 
+    /// or one of its parents) it belongs to.
+    fn lookup(&self, key: &Key) -> Option<(&Self, Arc<Entry<Key, SvcBase>>)> {
+        let mut ioc = self;
+        loop {
+            if let Some(entry) = ioc.services.get(key) {
+                return Some((ioc, entry));
+            }
+            match ioc.services.parent {
+                Some(ref parent) => ioc = parent,
+                None => return None,
+            }
+        }
+    }
+
+    /// Like `lookup`, for handing out references bound to `self`, see `Services::expose`.
+    fn expose(&self, key: &Key) -> Option<&Entry<Key, SvcBase>> {
+        let mut ioc = self;
+        loop {
+            if let Some(entry) = ioc.services.expose(key) {
+                return Some(entry);
+            }
+            ioc = ioc.services.parent.as_ref()?;
+        }
+    }
+
+    /// Returns a snapshot of the entries of all registered services ordered by key, 
+    /// together with the container they belong to.
+    fn visible_entries(&self) -> Vec<(&Self, Arc<Entry<Key, SvcBase>>)> {
+        let mut entries = BTreeMap::new();
+        let mut ioc = Some(self);
+        while let Some(cont) = ioc {
+            for entry in cont.services.entries() {
+                entries.entry(entry.key.clone()).or_insert((cont, entry));
+            }
+            ioc = cont.services.parent.as_ref();
+        }
+        entries.into_values().collect()
+    }
+
+    /// Returns the keys of all registered services, including ones which haven't 
+    /// been constructed yet.
+    pub fn keys(&self) -> impl Iterator<Item = Key> {
+        let keys: Vec<Key> = self.visible_entries().into_iter().map(|(_, entry)| entry.key.clone()).collect();
+        keys.into_iter()
+    }
+
+    /// Returns the locks of all constructed services, including those of parent
+    /// containers, see `get_service`.
+    pub fn services(&self) -> BTreeMap<&Key, &RwLock<Box<SvcBase>>> {
+        self.keys()
+            .filter_map(|key| self.expose(&key))
+            .filter_map(|entry| entry.get().map(|lock| (&entry.key, lock)))
+            .collect()
+    }
+
+    /// Returns the lock of the service registered under `key`.
+    ///
+    /// Lazy services are only returned once they have been constructed, use 
+    /// `read_service_base` and friends to construct them. Guards of the returned 
+    /// lock aren't included in `stats`. If the service is removed, it is kept until 
+    /// `purge_removed`, see `remove`.
+    pub fn get_service(&self, key: &Key) -> Option<&RwLock<Box<SvcBase>>> {
+        self.expose(key).and_then(Entry::get)
+    }
+
+    /// Returns the declared dependencies of the service registered under `key`.
+    ///
+    /// For services registered with a constructor these are the dependencies of its 
+    /// method, otherwise those of `reflect::Service::dependencies`.
+    pub fn dependencies(&self, key: &Key) -> Option<Vec<Dependency<Key>>> {
+        self.lookup(key).map(|(_, entry)| entry.deps())
+    }
+
+    /// Returns the lock statistics of all registered services, collected if enabled 
+    /// with `ContainerBuilder::collect_stats`.
+    ///
+    /// Services which aren't locked through this container, e.g. transient ones, 
+    /// report zeroes. Scopes share the statistics of services they don't own.
+    pub fn stats(&self) -> ContainerStats<Key> {
+        let services = self.visible_entries().into_iter()
+            .map(|(_, entry)| (entry.key.clone(), entry.counters.snapshot()))
+            .collect();
+        ContainerStats{ services }
+    }
+
+    /// Returns the threads which currently hold or wait for guards of the services, 
+    /// tracked if enabled with `ContainerBuilder::track_holders`:
+    ///
+    /// ```text
+    /// thread worker-7 holds Write<"cache"> since 30.0s at src/jobs.rs:42:17
+    /// thread main waits for Read<"cache"> since 2.1s at src/main.rs:10:5
+    /// ```
+    ///
+    /// Instances owned by scopes of the container aren't included.
+    pub fn lock_report(&self) -> LockReport<Key> {
+        let mut holders = Vec::new();
+        let mut waiters = Vec::new();
+        for (_, entry) in self.visible_entries() {
+            for (holder, waiting) in entry.holders.list().values() {
+                let list = if *waiting { &mut waiters } else { &mut holders };
+                list.push((entry.key.clone(), holder.clone()));
+            }
+        }
+        LockReport{ holders, waiters }
+    }
+
+    /// Returns the registered services and their declared dependencies, see 
+    /// `reflect::Service::dependencies`.
+    pub fn dependency_graph(&self) -> DependencyGraph<Key> {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for (_, entry) in self.visible_entries() {
+            let meta = entry.meta();
+            nodes.push(Node{ key: entry.key.clone(), type_name: meta.type_name, lifetime: entry.lifetime() });
+            edges.extend(meta.deps.iter().map(|dep| Edge{ 
+                from: entry.key.clone(), 
+                to: dep.key.clone(), 
+                access: dep.access 
+            }));
+        }
+        DependencyGraph{ nodes, edges }
+    }
+
+    /// Calls `Lifecycle::init` and then `Lifecycle::start` on all services with 
+    /// enabled lifecycle, see `ContainerBuilder::lifecycle`.
+    ///
+    /// Services are visited in the order of their declared dependencies. Lazy services 
+    /// are constructed first. All failures of a phase are collected, if there are any 
+    /// no service is started. Only services owned by this container are visited, i.e. 
+    /// a scope leaves those of its parents alone.
+    pub fn start_all(&self) -> Result<(), errors::LifecycleError<Key>> {
+        self.run_lifecycle(Phase::Init)?;
+        self.run_lifecycle(Phase::Start)
+    }
+
+    /// Calls `Lifecycle::stop` on all services with enabled lifecycle, dependents first.
+    ///
+    /// Lazy services which were never constructed are skipped, as are services of 
+    /// parent containers, see `start_all`. Failures are collected and don't stop the 
+    /// remaining services from being stopped.
+    pub fn stop_all(&self) -> Result<(), errors::LifecycleError<Key>> {
+        self.run_lifecycle(Phase::Stop)
+    }
+
+    fn run_lifecycle(&self, phase: Phase) -> Result<(), errors::LifecycleError<Key>> {
+        let (mut order, _) = self.dependency_order();
+        if phase == Phase::Stop {
+            order.reverse();
+        }
+        let mut failures = Vec::new();
+        for key in order {
+            let entry = match self.services.get(&key) {
+                Some(entry) => entry,
+                // owned by a parent
+                None => continue,
+            };
+            match (entry.meta().lifecycle, &entry.instance) {
+                // templates, without an instance in this container
+                (_, &Instance::Transient(_)) | (_, &Instance::Scoped(_)) | (None, _) => continue,
+                (Some(_), _) => {}
+            }
+            if phase == Phase::Stop && entry.get().is_none() {
+                continue;
+            }
+            let service = match ServiceLock::new(&key, entry, self, Acquire::Block) {
+                Ok(service) => service,
+                Err(err) => {
+                    failures.push((key.clone(), phase, Box::new(err.into_owned()) as Box<dyn StdError + Send + Sync>));
+                    continue;
+                }
+            };
+            let res = match service.write(&key, Acquire::Block) {
+                Ok(mut svc) => {
+                    // `replace` changes the hook while holding the write-lock
+                    let hook = service.entry.meta().lifecycle;
+                    hook.map_or(Ok(()), |hook| hook(&mut **svc, phase))
+                }
+                Err(err) => Err(Box::new(err.into_owned()) as Box<dyn StdError + Send + Sync>),
+            };
+            if let Err(err) = res {
+                failures.push((key.clone(), phase, err));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(errors::LifecycleError{ failures })
+        }
+    }
+
+    /// Returns all registered keys ordered such that declared dependencies come 
+    /// before their dependents, together with the dependency cycles which prevent 
+    /// a complete ordering.
+    fn dependency_order(&self) -> (Vec<Key>, Vec<Vec<Key>>) {
+        let deps: BTreeMap<Key, _> = self.visible_entries().into_iter()
+            .map(|(_, entry)| (entry.key.clone(), entry.deps()))
+            .collect();
+        let (order, cycles) = dependency_order(deps.keys(), &|key| deps.get(key).map(|deps| &deps[..]));
+        (order.into_iter().cloned().collect(), cycles)
+    }
+
+    /// Whether constructing the service `key` would require the service itself, 
+    /// following the declared dependencies of services which get constructed when 
+    /// resolved, see `Entry::is_pending`.
+    fn constructs_itself(&self, key: &Key) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![key];
+        while let Some(cur) = stack.pop() {
+            let entry = match self.lookup(cur) {
+                Some((_, entry)) => entry,
+                None => continue,
+            };
+            for dep in entry.deps() {
+                if dep.key == key {
+                    return true;
+                }
+                let pending = self.lookup(dep.key).is_some_and(|(_, entry)| entry.is_pending());
+                if pending && seen.insert(dep.key) {
+                    stack.push(dep.key);
+                }
+            }
+        }
+        false
+    }
+
+    /// Checks that all declared dependencies are registered and acyclic.
+    fn validate(&self) -> Result<(), errors::BuildError<Key>> {
+        let mut missing = Vec::new();
+        for (_, entry) in self.visible_entries() {
+            for dep in entry.deps() {
+                if self.lookup(dep.key).is_none() {
+                    missing.push((entry.key.clone(), dep.key.clone()));
+                }
+            }
+        }
+        let (_, cycles) = self.dependency_order();
+        if missing.is_empty() && cycles.is_empty() {
+            Ok(())
+        } else {
+            Err(errors::BuildError{ missing, cycles })
+        }
+    }
+
+    /// Records where the container has been called from, if holders are tracked. 
+    /// Only the outermost call of a thread is recorded.
+    #[track_caller]
+    fn enter(&self) -> Option<CallerScope> {
+        if self.services.holders { Some(CallerScope::enter(Location::caller())) } else { None }
+    }
+
+    /// Returns the lock of the service, constructing it first if necessary.
+    fn service_lock<'a>(
+        &'a self, 
+        key: &'a Key, 
+        acquire: Acquire
+    ) -> Result<ServiceLock<'a, Key, SvcBase>, Error<'a, Key>> {
+        match self.lookup(key) {
+            Some((owner, entry)) => ServiceLock::new(key, entry, owner, acquire),
+            None => Err(Error::NotFound{ key })
+        }
+    }
+
+    fn mismatched_type<'a, Svc: Any>(&self, key: &'a Key) -> Error<'a, Key> {
+        let found = self.lookup(key).map_or("?", |(_, entry)| entry.type_name());
+        mismatched_type::<_, Svc>(key, found)
+    }
+
+    #[track_caller]
+    pub fn read_service_base<'a>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<BaseReadGuard<'a, SvcBase>, Error<'a, Key>> {
+        let _caller = self.enter();
+        self.service_lock(key, Acquire::Block)?.read(key, Acquire::Block)
+    }
+
+    #[track_caller]
+    pub fn write_service_base<'a>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<BaseWriteGuard<'a, SvcBase>, Error<'a, Key>> {
+        let _caller = self.enter();
+        self.service_lock(key, Acquire::Block)?.write(key, Acquire::Block)
+    }
+
+    #[track_caller]
+    pub fn read_service<'a, Svc>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        let base = self.read_service_base(key)?;
+        ReadGuard::wrap(base).map_err(|_| self.mismatched_type::<Svc>(key))
+    }
+
+    #[track_caller]
+    pub fn write_service<'a, Svc>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        let base = self.write_service_base(key)?;
+        WriteGuard::wrap(base).map_err(|_| self.mismatched_type::<Svc>(key))
+    }
+
+    #[track_caller]
+    pub fn read<'a, Svc>(
+        &'a self
+    ) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.read_service(Svc::key())
+    }
+
+    #[track_caller]
+    pub fn write<'a, Svc>(
+        &'a self
+    ) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.write_service(Svc::key())
+    }
+
+    #[track_caller]
+    pub fn try_read_service_base<'a>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<BaseReadGuard<'a, SvcBase>, Error<'a, Key>> {
+        let _caller = self.enter();
+        self.service_lock(key, Acquire::Try)?.read(key, Acquire::Try)
+    }
+
+    #[track_caller]
+    pub fn try_write_service_base<'a>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<BaseWriteGuard<'a, SvcBase>, Error<'a, Key>> {
+        let _caller = self.enter();
+        self.service_lock(key, Acquire::Try)?.write(key, Acquire::Try)
+    }
+
+    #[track_caller]
+    pub fn try_read_service<'a, Svc>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        let base = self.try_read_service_base(key)?;
+        ReadGuard::wrap(base).map_err(|_| self.mismatched_type::<Svc>(key))
+    }
+
+    #[track_caller]
+    pub fn try_write_service<'a, Svc>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        let base = self.try_write_service_base(key)?;
+        WriteGuard::wrap(base).map_err(|_| self.mismatched_type::<Svc>(key))
+    }
+
+    #[track_caller]
+    pub fn try_read<'a, Svc>(
+        &'a self
+    ) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.try_read_service(Svc::key())
+    }
+
+    #[track_caller]
+    pub fn try_write<'a, Svc>(
+        &'a self
+    ) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.try_write_service(Svc::key())
+    }
+
+    /// Replaces the service registered under `key` by `svc` and returns the old instance.
+    ///
+    /// Waits for the write-lock, lazy services are constructed first. As the concrete 
+    /// type of `svc` is unknown here, errors will report it as `SvcBase` and it has 
+    /// no declared dependencies.
+    ///
+    /// Hooks enabled for the old instance are dropped with it, i.e. the returned 
+    /// instance isn't disposed and `svc` takes no part in `start_all` and `stop_all`. 
+    /// Use `insert_with` to enable hooks of a replacement.
+    #[track_caller]
+    pub fn replace_service<'a>(
+        &'a self, 
+        key: &'a Key, 
+        svc: Box<SvcBase>
+    ) -> Result<Box<SvcBase>, Error<'a, Key>> {
+        self.replace_with(key, svc, type_name::<SvcBase>(), Vec::new())
+    }
+
+    /// Replaces the service registered under `Svc::key()`, see `replace_service`.
+    ///
+    /// The old instance may be of a different type than `Svc`, the declared 
+    /// dependencies become those of `Svc`.
+    #[track_caller]
+    pub fn replace<'a, Svc>(
+        &'a self, 
+        svc: Svc
+    ) -> Result<Box<SvcBase>, Error<'a, Key>>
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    {
+        let mut deps = Vec::new();
+        Svc::dependencies(&mut deps);
+        self.replace_with(Svc::key(), svc.into(), type_name::<Svc>(), deps)
+    }
+
+    #[track_caller]
+    fn replace_with<'a>(
+        &'a self, 
+        key: &'a Key, 
+        svc: Box<SvcBase>, 
+        type_name: &'static str,
+        deps: Vec<Dependency<Key>>
+    ) -> Result<Box<SvcBase>, Error<'a, Key>> {
+        let _caller = self.enter();
+        let service = self.service_lock(key, Acquire::Block)?;
+        let mut guard = service.write(key, Acquire::Block)?;
+        *service.entry.meta() = Meta{ type_name, deps, lifecycle: None, dispose: None };
+        Ok(mem::replace(&mut *guard, svc))
+    }
+
+    /// Calls `f` until it stops failing with (a nested) `Error::WouldBlock`, giving up with 
+    /// `Error::Timeout` once `timeout` has passed since `start`.
+    #[track_caller]
+    fn retry<'a, T>(
+        &'a self, 
+        start: Instant,
+        timeout: Duration, 
+        mut f: impl FnMut() -> Result<T, Error<'a, Key>>
+    ) -> Result<T, Error<'a, Key>> {
+        let _caller = self.enter();
+        let mut backoff = Duration::from_micros(10);
+        loop {
+            match f() {
+                Err(err) if matches!(*err.root(), Error::WouldBlock{ .. }) => {
+                    let waited = start.elapsed();
+                    if waited >= timeout {
+                        return Err(err.map_root(|root| Error::Timeout{ key: root.key(), waited }));
+                    }
+                    thread::sleep(cmp::min(backoff, timeout - waited));
+                    backoff = cmp::min(backoff * 2, Duration::from_millis(5));
+                }
+                res => return res,
+            }
+        }
+    }
+
+    #[track_caller]
+    pub fn read_service_timeout<'a, Svc>(
+        &'a self, 
+        key: &'a Key,
+        timeout: Duration
+    ) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        self.retry(Instant::now(), timeout, || self.try_read_service(key))
+    }
+
+    /// Like `write_service`, but gives up with `Error::Timeout` after `timeout`.
+    ///
+    /// The lock is polled, so readers which keep the service locked between them 
+    /// can starve the writer.
+    #[track_caller]
+    pub fn write_service_timeout<'a, Svc>(
+        &'a self, 
+        key: &'a Key,
+        timeout: Duration
+    ) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        self.retry(Instant::now(), timeout, || self.try_write_service(key))
+    }
+
+    #[track_caller]
+    pub fn read_timeout<'a, Svc>(
+        &'a self,
+        timeout: Duration
+    ) -> Result<ReadGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.read_service_timeout(Svc::key(), timeout)
+    }
+
+    #[track_caller]
+    pub fn write_timeout<'a, Svc>(
+        &'a self,
+        timeout: Duration
+    ) -> Result<WriteGuard<'a, Svc, SvcBase>, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.write_service_timeout(Svc::key(), timeout)
+    }
+
+    #[track_caller]
+    pub fn create<'a, Obj>(
+        &'a self
+    ) -> Result<Obj, Error<'a, Key>>
+    where
+        Obj: reflect::FactoryObject,
+        Obj::Factory: reflect::Service<Key = Key> + FactoryBase<'a, Key, SvcBase, Obj>,
+        SvcBase: Downcast<Obj::Factory>,
+    {
+        let _caller = self.enter();
+        <Obj::Factory as FactoryBase<'a, Key, SvcBase, Obj>>::create(self, Acquire::Block)
+    }
+
+    #[track_caller]
+    pub fn try_create<'a, Obj>(
+        &'a self
+    ) -> Result<Obj, Error<'a, Key>>
+    where
+        Obj: reflect::FactoryObject,
+        Obj::Factory: reflect::Service<Key = Key> + FactoryBase<'a, Key, SvcBase, Obj>,
+        SvcBase: Downcast<Obj::Factory>,
+    {
+        let _caller = self.enter();
+        <Obj::Factory as FactoryBase<'a, Key, SvcBase, Obj>>::create(self, Acquire::Try)
+    }
+
+    #[track_caller]
+    fn make_service_with<'a, Svc>(
+        &'a self, 
+        key: &'a Key,
+        acquire: Acquire
+    ) -> Result<Svc, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        let _caller = self.enter();
+        let entry = match self.lookup(key) {
+            Some((_, entry)) => entry,
+            None => return Err(Error::NotFound{ key }),
+        };
+        let ctor = match entry.instance {
+            Instance::Transient(ref ctor) => ctor,
+            _ => return Err(Error::MismatchedLifetime{
+                key,
+                expected: Lifetime::Transient,
+                found: entry.lifetime(),
+            }),
+        };
+        match ctor(self, acquire)?.downcast() {
+            Ok(svc) => Ok(*svc),
+            Err(_) => Err(mismatched_type::<_, Svc>(key, entry.type_name())),
+        }
+    }
+
+    #[track_caller]
+    pub fn make_service<'a, Svc>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<Svc, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        self.make_service_with(key, Acquire::Block)
+    }
+
+    #[track_caller]
+    pub fn try_make_service<'a, Svc>(
+        &'a self, 
+        key: &'a Key
+    ) -> Result<Svc, Error<'a, Key>>
+        where Svc: Any, SvcBase: Downcast<Svc>
+    {
+        self.make_service_with(key, Acquire::Try)
+    }
+
+    /// Constructs a new instance of a service registered with `register_transient`.
+    #[track_caller]
+    pub fn make<'a, Svc>(
+        &'a self
+    ) -> Result<Svc, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.make_service(Svc::key())
+    }
+
+    #[track_caller]
+    pub fn try_make<'a, Svc>(
+        &'a self
+    ) -> Result<Svc, Error<'a, Key>>
+        where Svc: reflect::Service<Key = Key>, SvcBase: Downcast<Svc>
+    {
+        self.try_make_service(Svc::key())
+    }
+
+    /// Acquires the locks of `deps` in key-order, returning their guards in the 
+    /// order of `deps`.
+    fn lock<'a>(
+        &'a self, 
+        deps: &[Dependency<Key>], 
+        acquire: Acquire
+    ) -> Result<Guards<'a, Key, SvcBase>, Error<'a, Key>> {
+        let deps: Vec<_> = deps.iter().filter(|dep| dep.access.is_lock()).collect();
+
+        // Construct lazy services first, so no locks are held while their dependencies 
+        // get resolved.
+        let mut locks = Vec::with_capacity(deps.len());
+        for dep in &deps {
+            locks.push(self.service_lock(dep.key, acquire)?);
+        }
+
+        let mut order: Vec<usize> = (0..deps.len()).collect();
+        order.sort_by_key(|&idx| deps[idx].key);
+
+        let mut guards: Vec<_> = deps.iter().map(|_| None).collect();
+        for idx in order {
+            let key = deps[idx].key;
+            let service = &locks[idx];
+            let guard = match deps[idx].access {
+                Access::Read => BaseGuard::Read(service.read(key, acquire)?),
+                Access::Write => BaseGuard::Write(service.write(key, acquire)?),
+                Access::Create => unreachable!(),
+            };
+            guards[idx] = Some((key, service.entry.type_name(), guard));
+        }
+        let guards: Vec<_> = guards.into_iter().map(Option::unwrap).collect();
+        Ok(Guards{ inner: guards.into_iter() })
+    }
+
+    #[doc(hidden)]
+    #[track_caller]
+    pub fn resolve_with<'a, M>(&'a self, acquire: Acquire) -> Result<M::Ret, Error<'a, Key>>
+        where M: Method<'a, Key, SvcBase>
+    {
+        self.resolve_until::<M>(acquire, None)
+    }
+
+    /// Resolves `M`, retrying in `Acquire::Try` mode for up to `timeout` if given.
+    #[track_caller]
+    fn resolve_until<'a, M>(&'a self, acquire: Acquire, timeout: Option<Duration>) -> Result<M::Ret, Error<'a, Key>>
+        where M: Method<'a, Key, SvcBase>
+    {
+        let _caller = self.enter();
+        let mut deps = Vec::new();
+        M::dependencies(&mut deps);
+
+        #[cfg(feature = "tracing")]
+        let span = debug_span!("resolve", method = ::std::any::type_name::<M>(), acquire = ?acquire);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let res = match self.services.observer {
+            Some(ref observer) => {
+                observer.resolve_started(&deps);
+                let start = Instant::now();
+                let res = self.resolve_deps::<M>(&deps, acquire, timeout);
+                if let Err(ref err) = res {
+                    observer.resolve_failed(err, start.elapsed());
+                }
+                res
+            }
+            None => self.resolve_deps::<M>(&deps, acquire, timeout),
+        };
+        #[cfg(feature = "tracing")]
+        {
+            if let Err(ref err) = res {
+                debug!(error = %err, "resolve failed");
+            }
+        }
+        res
+    }
+
+    fn resolve_deps<'a, M>(
+        &'a self, 
+        deps: &[Dependency<Key>], 
+        acquire: Acquire, 
+        timeout: Option<Duration>
+    ) -> Result<M::Ret, Error<'a, Key>>
+        where M: Method<'a, Key, SvcBase>
+    {
+        check_conflicts(deps)?;
+
+        let (prep, mut guards) = match timeout {
+            None => (M::prepare(self, acquire)?, self.lock(deps, acquire)?),
+            Some(timeout) => {
+                // Objects are created once, only acquiring the locks is retried.
+                let start = Instant::now();
+                let prep = self.retry(start, timeout, || M::prepare(self, Acquire::Try))?;
+                (prep, self.retry(start, timeout, || self.lock(deps, Acquire::Try))?)
+            }
+        };
+        M::finish(prep, &mut guards)
+    }
+
+    /// Resolves `M`, waiting for services which are currently locked.
+    ///
+    /// All locks of `M` are acquired in the order of their keys, regardless of their 
+    /// order in `M`, so concurrent resolves can't deadlock each other. This doesn't 
+    /// extend to guards which are already held by the calling thread.
+    ///
+    /// Fails with `Error::ConflictingAccess` if `M` locks a service more than once and 
+    /// at least one of those is `Write`, e.g. `Write<(A, A)>` or `(Read<A>, Write<A>)`.
+    ///
+    /// With the `tracing` feature every resolve is a `resolve` span, in which each 
+    /// acquired guard emits a `lock acquired` event with the time waited for it. Held 
+    /// guards are `guard` spans, closed when the guard is released.
+    #[track_caller]
+    pub fn resolve<'a, M>(&'a self) -> Result<M::Ret, Error<'a, Key>>
+        where M: Method<'a, Key, SvcBase>
+    {
+        self.resolve_with::<M>(Acquire::Block)
+    }
+
+    /// Like `resolve`, but fails with `Error::WouldBlock` instead of waiting for a 
+    /// service. Guards acquired up to that point are released again.
+    #[track_caller]
+    pub fn try_resolve<'a, M>(&'a self) -> Result<M::Ret, Error<'a, Key>>
+        where M: Method<'a, Key, SvcBase>
+    {
+        self.resolve_with::<M>(Acquire::Try)
+    }
+
+    /// Like `try_resolve`, but keeps retrying for up to `timeout` before failing 
+    /// with `Error::Timeout`. No guards are held between attempts.
+    ///
+    /// The objects of `Create` and `Make` are created before the locks are retried, 
+    /// so their constructors only run again if creating them would block itself, 
+    /// e.g. because a factory's `Args` are locked. As locks are polled rather than 
+    /// waited for, a writer may time out while readers keep a service locked, even 
+    /// if none of them holds it for long.
+    #[track_caller]
+    pub fn resolve_timeout<'a, M>(&'a self, timeout: Duration) -> Result<M::Ret, Error<'a, Key>>
+        where M: Method<'a, Key, SvcBase>
+    {
+        self.resolve_until::<M>(Acquire::Try, Some(timeout))
+    }
+}
+
+// ++++++++++++++++++++ ContainerBuilder ++++++++++++++++++++
+
+pub struct ContainerBuilder<Key: reflect::Key, SvcBase: ?Sized + Any> {
+    cont: Container<Key, SvcBase>
+}
+
+impl<Key, SvcBase: ?Sized> ContainerBuilder<Key, SvcBase>
+    where Key: reflect::Key, SvcBase: Any
+{
+    pub fn new() -> Self {
+        ContainerBuilder{ cont: Container::new() }
+    }
+
+    pub fn register_service(&mut self, key: Key, svc: Box<SvcBase>) -> &mut Self {
+        self.cont.register_service(key, svc);
+        self
+    }
+
+    /// Like `register_service`, but errors report the concrete type as `type_name` and 
+    /// the service depends on `deps`.
+    #[doc(hidden)]
+    pub fn register_boxed(
+        &mut self, 
+        key: Key, 
+        svc: Box<SvcBase>, 
+        type_name: &'static str, 
+        deps: Vec<Dependency<Key>>
+    ) -> &mut Self {
+        self.cont.register_entry(key, Instance::Singleton(RwLock::new(svc)), type_name, deps);
+        self
+    }
+
+    /// NOTE: The `Box<Svc>: Into<Box<Base>>`-clause is needed due to rusts lack of 
+    /// HKT or a `Coercible`-trait (to name two solutions).
+    pub fn register<Svc>(&mut self, svc: Svc) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    {
+        self.cont.register::<Svc>(svc);
+        self
+    }
+
+    pub fn register_default<Svc>(&mut self) -> &mut Self
+    where
+        Svc: Default + reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+    {
+        self.cont.register_default::<Svc>();
+        self
+    }
+
+    /// Registers a singleton which gets constructed the first time it is resolved.
+    ///
+    /// `Deps` gets resolved and passed to `ctor`, errors returned by `ctor` are 
+    /// reported as `Error::CreationError`. If construction fails it is retried on 
+    /// the next resolve.
+    pub fn register_lazy<Svc, Deps, E>(
+        &mut self, 
+        ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        Deps: for<'a> Method<'a, Key, SvcBase>,
+        E: StdError + Send + Sync + 'static,
+    {
+        self.cont.register_lazy::<Svc, Deps, E>(ctor);
+        self
+    }
+
+    /// Registers a service of which every resolve constructs a new instance, see 
+    /// `ioc::Make`.
+    ///
+    /// `Deps` gets resolved and passed to `ctor` each time, errors returned by 
+    /// `ctor` are reported as `Error::CreationError`.
+    pub fn register_transient<Svc, Deps, E>(
+        &mut self, 
+        ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        Deps: for<'a> Method<'a, Key, SvcBase>,
+        E: StdError + Send + Sync + 'static,
+    {
+        self.cont.register_transient::<Svc, Deps, E>(ctor);
+        self
+    }
+
+    /// Registers a service which gets constructed once per `Container::scope`.
+    ///
+    /// `Deps` gets resolved from the scope, so scoped services may depend on each 
+    /// other as well as on services of the parent container.
+    pub fn register_scoped<Svc, Deps, E>(
+        &mut self, 
+        ctor: impl for<'a> Fn(<Deps as Method<'a, Key, SvcBase>>::Ret) -> Result<Svc, E> + Send + Sync + 'static
+    ) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Into<Box<SvcBase>>,
+        Deps: for<'a> Method<'a, Key, SvcBase>,
+        E: StdError + Send + Sync + 'static,
+    {
+        self.cont.register_scoped::<Svc, Deps, E>(ctor);
+        self
+    }
+
+    /// Enables the `Lifecycle` hooks of `Svc` for `Container::start_all` and 
+    /// `Container::stop_all`.
+    ///
+    /// Panics if `Svc` has not been registered yet.
+    pub fn lifecycle<Svc>(&mut self) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Lifecycle,
+        SvcBase: Downcast<Svc>,
+    {
+        match self.cont.services_mut().entries_mut().get_mut(Svc::key()) {
+            Some(entry) => entry.meta().lifecycle = Some(lifecycle_hook::<Key, SvcBase, Svc>),
+            None => panic!("service {:?} has to be registered before enabling its lifecycle", Svc::key()),
+        }
+        self
+    }
+
+    /// Enables the `Dispose` hook of `Svc`, called when the container is dropped.
+    ///
+    /// Panics if `Svc` has not been registered yet.
+    pub fn dispose<Svc>(&mut self) -> &mut Self
+    where
+        Svc: reflect::Service<Key = Key> + Dispose,
+        SvcBase: Downcast<Svc>,
+    {
+        match self.cont.services_mut().entries_mut().get_mut(Svc::key()) {
+            Some(entry) => entry.meta().dispose = Some(dispose_hook::<SvcBase, Svc>),
+            None => panic!("service {:?} has to be registered before enabling its disposal", Svc::key()),
+        }
+        self
+    }
+
+    /// Enables collecting lock statistics, see `Container::stats`. Scopes of the 
+    /// container inherit this.
+    pub fn collect_stats(&mut self) -> &mut Self {
+        self.cont.services_mut().stats = true;
+        self
+    }
+
+    /// Installs `observer`, replacing a previously installed one. Scopes of the 
+    /// container inherit it.
+    pub fn observe(&mut self, observer: impl Observer<Key> + 'static) -> &mut Self {
+        self.cont.services_mut().observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Enables tracking which threads hold or wait for guards of the services, see 
+    /// `Container::lock_report`. Scopes of the container inherit this.
+    ///
+    /// Holders are recorded with the location the container has been called from 
+    /// and the label of `label_locks`, if any.
+    pub fn track_holders(&mut self) -> &mut Self {
+        self.cont.services_mut().holders = true;
+        self
+    }
+
+    pub fn build(self) -> Container<Key, SvcBase> {
+        self.cont
+    }
+
+    /// Like `build`, but first checks the declared dependencies of all services, see 
+    /// `reflect::Service::dependencies`.
+    ///
+    /// All unregistered dependencies and all dependency cycles are reported at once.
+    pub fn try_build(self) -> Result<Container<Key, SvcBase>, errors::BuildError<Key>> {
+        self.cont.validate()?;
+        Ok(self.cont)
+    }
+}
+
+impl<Key, SvcBase: ?Sized> Default for ContainerBuilder<Key, SvcBase> 
+    where Key: reflect::Key, SvcBase: Any
+{
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diagnostics::label_locks;
+    use errors::{DummyError, OwnedError};
+    use factory::Factory;
+    use methods::{Create, Make, Read, Write};
+    use reflect::FactoryObject;
+    use testing::{Base, Builder};
+
+    use std::fmt;
+    use std::sync::mpsc;
+
+    struct A;
+    service!(A, "a");
+
+    struct B;
+    service!(B, "b");
+
+    #[test]
+    fn lazy_dependencies_are_constructed_first() {
+        let mut builder = Builder::new();
+        builder.register_lazy::<A, Read<B>, DummyError>(|_| Ok(A));
+        builder.register_lazy::<B, (), DummyError>(|()| Ok(B));
+        let ioc = builder.build();
+        assert!(ioc.read::<A>().is_ok());
+        assert!(ioc.get_service(&"b").is_some());
+    }
+
+    #[test]
+    fn lazy_cycle_fails_instead_of_hanging() {
+        let mut builder = Builder::new();
+        builder.register_lazy::<A, Read<B>, DummyError>(|_| Ok(A));
+        builder.register_lazy::<B, Read<A>, DummyError>(|_| Ok(B));
+        let ioc = builder.build();
+        match ioc.read::<A>() {
+            Err(ref err) => assert!(matches!(*err.root(), Error::Cycle{ key: &"a" })),
+            Ok(_) => panic!("constructed a cyclic service"),
+        }
+        assert!(ioc.get_service(&"a").is_none());
+    }
+
+    #[test]
+    fn lazy_cycle_fails_across_threads() {
+        let mut builder = Builder::new();
+        builder.register_lazy::<A, Read<B>, DummyError>(|_| Ok(A));
+        builder.register_lazy::<B, Read<A>, DummyError>(|_| Ok(B));
+        let ioc = builder.build();
+        thread::scope(|s| {
+            let a = s.spawn(|| ioc.read::<A>().is_err());
+            let b = s.spawn(|| ioc.read::<B>().is_err());
+            assert!(a.join().unwrap() && b.join().unwrap());
+        });
+    }
+
+    fn would_block<T>(res: Result<T, Error<&'static str>>, key: &str) -> bool {
+        match res {
+            Err(ref err) => matches!(*err.root(), Error::WouldBlock{ key: k } if *k == key),
+            Ok(_) => false,
+        }
+    }
+
+    #[test]
+    fn try_methods_would_block() {
+        let mut builder = Builder::new();
+        builder.register(A);
+        let ioc = builder.build();
+
+        let read = ioc.read::<A>().unwrap();
+        assert!(ioc.try_read::<A>().is_ok());
+        assert!(would_block(ioc.try_write::<A>(), "a"));
+        assert!(would_block(ioc.try_resolve::<Write<A>>(), "a"));
+        drop(read);
+
+        let write = ioc.write::<A>().unwrap();
+        assert!(would_block(ioc.try_read::<A>(), "a"));
+        assert!(would_block(ioc.try_read_service_base(&"a"), "a"));
+        assert!(would_block(ioc.try_resolve::<Read<A>>(), "a"));
+        drop(write);
+
+        assert!(ioc.try_write::<A>().is_ok());
+    }
+
+    #[test]
+    fn try_resolve_releases_acquired_guards() {
+        let mut builder = Builder::new();
+        builder.register(A);
+        builder.register(B);
+        let ioc = builder.build();
+
+        let b = ioc.write::<B>().unwrap();
+        // "a" is locked before "b", so its guard has been taken when "b" fails.
+        assert!(would_block(ioc.try_resolve::<(Write<A>, Read<B>)>(), "b"));
+        assert!(ioc.try_write::<A>().is_ok());
+        drop(b);
+
+        assert!(ioc.try_resolve::<(Write<A>, Read<B>)>().is_ok());
+    }
+
+    #[test]
+    fn resolve_timeout_reports_waited() {
+        let mut builder = Builder::new();
+        builder.register(A);
+        let ioc = builder.build();
+
+        let _a = ioc.write::<A>().unwrap();
+        match ioc.resolve_timeout::<Read<A>>(Duration::from_millis(20)) {
+            Err(ref err) => match *err.root() {
+                Error::Timeout{ key, waited } => {
+                    assert_eq!(*key, "a");
+                    assert!(waited >= Duration::from_millis(20));
+                }
+                ref root => panic!("unexpected error: {}", root),
+            },
+            Ok(_) => panic!("acquired a locked service"),
+        };
+    }
+
+    #[test]
+    fn resolve_timeout_creates_objects_once() {
+        static MADE: AtomicUsize = AtomicUsize::new(0);
+        let mut builder = Builder::new();
+        builder.register(A);
+        builder.register_transient::<B, (), DummyError>(|()| {
+            MADE.fetch_add(1, Ordering::SeqCst);
+            Ok(B)
+        });
+        let ioc = builder.build();
+
+        let _a = ioc.write::<A>().unwrap();
+        assert!(ioc.resolve_timeout::<(Make<B>, Read<A>)>(Duration::from_millis(20)).is_err());
+        assert_eq!(MADE.load(Ordering::SeqCst), 1);
+    }
+
+    struct X;
+    service!(X, "x");
+    struct Slow;
+    service!(Slow, "s");
+    struct F;
+    service!(F, "z");
+    struct Obj;
+
+    impl FactoryObject for Obj {
+        type Factory = F;
+    }
+
+    impl<'a> Factory<'a, &'static str, dyn Base, Obj> for F {
+        type Args = (Read<Slow>, Write<X>);
+        type Error = DummyError;
+
+        fn create(&self, _: (ReadGuard<'a, Slow, dyn Base>, WriteGuard<'a, X, dyn Base>)) -> Result<Obj, DummyError> {
+            Ok(Obj)
+        }
+    }
+
+    #[test]
+    fn create_locks_factory_in_key_order() {
+        let mut builder = Builder::new();
+        builder.register(X);
+        builder.register(F);
+        builder.register_lazy::<Slow, (), DummyError>(|()| {
+            thread::sleep(Duration::from_millis(50));
+            Ok(Slow)
+        });
+        // Leaked, so deadlocked threads fail the test rather than hanging it.
+        let ioc: &'static _ = Box::leak(Box::new(builder.build()));
+        let (tx, rx) = mpsc::channel();
+        let create = tx.clone();
+        thread::spawn(move || create.send(ioc.resolve::<Create<Obj>>().is_ok()));
+        thread::sleep(Duration::from_millis(10));
+        // Waits for the factory "z" while holding "x", which `Create` needs as well.
+        thread::spawn(move || tx.send(ioc.resolve::<Write<(X, F)>>().is_ok()));
+        for _ in 0..2 {
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(true));
+        }
+    }
+
+    fn conflicting<T>(res: Result<T, Error<&'static str>>) -> bool {
+        match res {
+            Err(ref err) => matches!(*err.root(), Error::ConflictingAccess{ key: &"a" }),
+            Ok(_) => false,
+        }
+    }
+
+    #[test]
+    fn conflicting_access_is_refused() {
+        let mut builder = Builder::new();
+        builder.register(A);
+        let ioc = builder.build();
+
+        assert!(conflicting(ioc.resolve::<Write<(A, A)>>()));
+        assert!(conflicting(ioc.resolve::<(Read<A>, Write<A>)>()));
+        assert!(conflicting(ioc.try_resolve::<(Write<A>, Read<A>)>()));
+        assert!(ioc.resolve::<(Read<A>, Read<A>)>().is_ok());
+        assert!(ioc.resolve::<Read<(A, A)>>().is_ok());
+    }
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    struct Top(Log, bool);
+    service!(Top, "top", Read<Mid>);
+    struct Mid(Log);
+    service!(Mid, "mid", Read<Bottom>);
+    struct Bottom(Log);
+    service!(Bottom, "bottom");
+
+    impl Dispose for Top {
+        fn dispose(&mut self) {
+            self.0.lock().unwrap().push("top");
+            assert!(!self.1, "failed to dispose top");
+        }
+    }
+
+    impl Dispose for Mid {
+        fn dispose(&mut self) { self.0.lock().unwrap().push("mid"); }
+    }
+
+    impl Dispose for Bottom {
+        fn dispose(&mut self) { self.0.lock().unwrap().push("bottom"); }
+    }
+
+    fn layered(log: &Log, panics: bool) -> Container<&'static str, dyn Base> {
+        // Registered top-down, so reverse registration order would dispose `Bottom` first.
+        let mut builder = Builder::new();
+        builder.register(Top(log.clone(), panics)).dispose::<Top>();
+        builder.register(Mid(log.clone())).dispose::<Mid>();
+        builder.register(Bottom(log.clone())).dispose::<Bottom>();
+        builder.build()
+    }
+
+    #[test]
+    fn dispose_follows_dependencies() {
+        let log = Log::default();
+        drop(layered(&log, false));
+        assert_eq!(*log.lock().unwrap(), ["top", "mid", "bottom"]);
+    }
+
+    #[test]
+    fn dispose_continues_after_panic() {
+        let log = Log::default();
+        let ioc = layered(&log, true);
+        assert!(panic::catch_unwind(AssertUnwindSafe(move || drop(ioc))).is_err());
+        assert_eq!(*log.lock().unwrap(), ["top", "mid", "bottom"]);
+    }
+
+    #[test]
+    fn purge_removed_disposes_inserted_services() {
+        let log = Log::default();
+        let mut ioc = Builder::new().build();
+        ioc.insert_with(|b| { b.register(Bottom(log.clone())).dispose::<Bottom>(); });
+        ioc.insert_with(|b| { b.register(Mid(log.clone())).dispose::<Mid>(); });
+        assert!(ioc.remove(&"mid"));
+        assert_eq!(*log.lock().unwrap(), ["mid"]);
+
+        assert!(ioc.get_service(&"bottom").is_some());
+        ioc.insert(Bottom(log.clone()));
+        let scope = ioc.scope();
+        assert!(!ioc.purge_removed());
+        drop(scope);
+        assert_eq!(*log.lock().unwrap(), ["mid"]);
+
+        assert!(ioc.purge_removed());
+        assert_eq!(*log.lock().unwrap(), ["mid", "bottom"]);
+        assert!(ioc.read::<Bottom>().is_ok());
+    }
+
+    #[test]
+    fn removed_services_are_disposed_with_their_last_guard() {
+        let log = Log::default();
+        let ioc = Arc::new(Builder::new().build());
+        ioc.insert_with(|b| { b.register(Bottom(log.clone())).dispose::<Bottom>(); });
+
+        let guard = ioc.read::<Bottom>().unwrap();
+        let shared = ioc.clone();
+        thread::spawn(move || assert!(shared.remove(&"bottom"))).join().unwrap();
+        assert!(matches!(ioc.read::<Bottom>(), Err(Error::NotFound{ .. })));
+        assert!(log.lock().unwrap().is_empty());
+
+        drop(guard);
+        assert_eq!(*log.lock().unwrap(), ["bottom"]);
+    }
+
+    struct First(Log);
+    service!(First, "replaced");
+    struct Second(Log);
+    service!(Second, "replaced", Read<Bottom>);
+
+    impl Lifecycle for First {
+        type Error = DummyError;
+
+        fn start(&mut self) -> Result<(), DummyError> {
+            self.0.lock().unwrap().push("start first");
+            Ok(())
+        }
+    }
+
+    impl Dispose for First {
+        fn dispose(&mut self) {
+            self.0.lock().unwrap().push("first");
+        }
+    }
+
+    impl Dispose for Second {
+        fn dispose(&mut self) {
+            self.0.lock().unwrap().push("second");
+        }
+    }
+
+    #[test]
+    fn replace_drops_hooks_of_the_old_type() {
+        let log = Log::default();
+        let mut builder = Builder::new();
+        builder.register(Bottom(log.clone()));
+        builder.register(First(log.clone())).lifecycle::<First>().dispose::<First>();
+        let ioc = builder.build();
+
+        let old = ioc.replace(Second(log.clone())).unwrap();
+        assert!(Downcast::<First>::downcast(old).is_ok());
+        assert_eq!(ioc.dependencies(&"replaced").unwrap(), [Dependency{ key: &"bottom", access: Access::Read }]);
+        assert!(ioc.start_all().is_ok());
+        assert!(ioc.stop_all().is_ok());
+        assert!(ioc.remove(&"replaced"));
+        assert!(log.lock().unwrap().is_empty());
+
+        ioc.insert_with(|b| { b.register(Second(log.clone())).dispose::<Second>(); });
+        drop(ioc);
+        assert_eq!(*log.lock().unwrap(), ["second"]);
+    }
+
+    #[derive(Debug)]
+    struct Failed;
+
+    impl fmt::Display for Failed {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            fmt.write_str("failed")
+        }
+    }
+
+    impl StdError for Failed {}
+
+    /// Logs the calls of `Lifecycle` hooks, failing in the given phase instead.
+    struct Hooks(Log, Option<Phase>);
+
+    impl Hooks {
+        fn call(&self, phase: Phase, entry: &'static str) -> Result<(), Failed> {
+            if self.1 == Some(phase) {
+                return Err(Failed);
+            }
+            self.0.lock().unwrap().push(entry);
+            Ok(())
+        }
+    }
+
+    struct Server(Hooks);
+    service!(Server, "server", Read<Store>);
+    struct Store(Hooks);
+    service!(Store, "store");
+
+    impl Lifecycle for Server {
+        type Error = Failed;
+
+        fn init(&mut self) -> Result<(), Failed> { self.0.call(Phase::Init, "init server") }
+        fn start(&mut self) -> Result<(), Failed> { self.0.call(Phase::Start, "start server") }
+        fn stop(&mut self) -> Result<(), Failed> { self.0.call(Phase::Stop, "stop server") }
+    }
+
+    impl Lifecycle for Store {
+        type Error = Failed;
+
+        fn init(&mut self) -> Result<(), Failed> { self.0.call(Phase::Init, "init store") }
+        fn start(&mut self) -> Result<(), Failed> { self.0.call(Phase::Start, "start store") }
+        fn stop(&mut self) -> Result<(), Failed> { self.0.call(Phase::Stop, "stop store") }
+    }
+
+    /// `Server` fails in `fails`, `Store` is lazy.
+    fn lifecycles(log: &Log, fails: Option<Phase>) -> Container<&'static str, dyn Base> {
+        let mut builder = Builder::new();
+        builder.register(Server(Hooks(log.clone(), fails))).lifecycle::<Server>();
+        let store_log = log.clone();
+        builder.register_lazy::<Store, (), Failed>(move |()| Ok(Store(Hooks(store_log.clone(), None))))
+            .lifecycle::<Store>();
+        builder.build()
+    }
+
+    fn failures(res: Result<(), errors::LifecycleError<&'static str>>) -> Vec<(&'static str, Phase)> {
+        res.unwrap_err().failures.into_iter().map(|(key, phase, _)| (key, phase)).collect()
+    }
+
+    #[test]
+    fn lifecycle_follows_dependencies() {
+        let log = Log::default();
+        let ioc = lifecycles(&log, None);
+        assert!(ioc.start_all().is_ok());
+        assert!(ioc.stop_all().is_ok());
+        assert_eq!(*log.lock().unwrap(), ["init store", "init server", "start store", "start server", "stop server", "stop store"]);
+
+        // Scopes leave the services of their parents alone.
+        log.lock().unwrap().clear();
+        let scope = ioc.scope();
+        assert!(scope.start_all().is_ok());
+        assert!(scope.stop_all().is_ok());
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn lifecycle_failures_are_collected() {
+        let log = Log::default();
+        let ioc = lifecycles(&log, Some(Phase::Init));
+        assert_eq!(failures(ioc.start_all()), [("server", Phase::Init)]);
+        assert_eq!(*log.lock().unwrap(), ["init store"]);
+
+        log.lock().unwrap().clear();
+        let ioc = lifecycles(&log, Some(Phase::Stop));
+        assert!(ioc.start_all().is_ok());
+        log.lock().unwrap().clear();
+        assert_eq!(failures(ioc.stop_all()), [("server", Phase::Stop)]);
+        assert_eq!(*log.lock().unwrap(), ["stop store"]);
+    }
+
+    #[test]
+    fn stats_count_guards_and_failures() {
+        let mut builder = Builder::new();
+        builder.register(A).register(B).collect_stats();
+        let ioc = builder.build();
+
+        drop(ioc.read::<A>().unwrap());
+        {
+            let _guard = ioc.write::<A>().unwrap();
+            assert!(would_block(ioc.try_read::<A>(), "a"));
+        }
+        let poisoned = thread::scope(|s| s.spawn(|| {
+            let _guard = ioc.write::<B>().unwrap();
+            panic!("poisoning b");
+        }).join());
+        assert!(poisoned.is_err());
+        assert!(matches!(ioc.read::<B>(), Err(Error::Poisoned{ .. })));
+
+        let stats = ioc.stats();
+        let a = stats.services[&"a"];
+        assert_eq!(a, LockStats{ reads: 1, writes: 1, would_block: 1, ..a });
+        let b = stats.services[&"b"];
+        assert_eq!(b, LockStats{ writes: 1, poisoned: 1, ..b });
+
+        let prometheus = stats.to_prometheus();
+        let lines: Vec<&str> = prometheus.lines().collect();
+        for line in &[
+            "# TYPE ioc_lock_acquired_total counter",
+            r#"ioc_lock_acquired_total{key="\"a\"",access="read"} 1"#,
+            r#"ioc_lock_acquired_total{key="\"a\"",access="write"} 1"#,
+            "# TYPE ioc_lock_hold_seconds_max gauge",
+            r#"ioc_lock_failed_total{key="\"a\"",reason="would_block"} 1"#,
+            r#"ioc_lock_failed_total{key="\"b\"",reason="poisoned"} 1"#,
+        ] {
+            assert!(lines.contains(line), "missing {}", line);
+        }
+    }
+
+    #[test]
+    fn lock_report_tracks_holders_and_waiters() {
+        let mut builder = Builder::new();
+        builder.register(A).track_holders();
+        let ioc = builder.build();
+
+        let label = label_locks("main");
+        let (guard, line) = (ioc.write::<A>().unwrap(), line!());
+        drop(label);
+        let report = ioc.lock_report();
+        assert!(report.waiters.is_empty());
+        let (key, ref holder) = report.holders[0];
+        assert_eq!(key, "a");
+        assert_eq!(holder.thread, thread::current().id());
+        assert_eq!(holder.access, Access::Write);
+        assert_eq!(holder.label.as_deref(), Some("main"));
+        let location = holder.location.unwrap();
+        assert_eq!((location.file(), location.line()), (file!(), line));
+
+        let (acquired_tx, acquired_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        thread::scope(|s| {
+            let ioc = &ioc;
+            thread::Builder::new().name("worker".to_owned()).spawn_scoped(s, move || {
+                let _label = label_locks("worker");
+                let _guard = ioc.read::<A>().unwrap();
+                acquired_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+            }).unwrap();
+
+            let start = Instant::now();
+            while ioc.lock_report().waiters.is_empty() {
+                assert!(start.elapsed() < Duration::from_secs(5), "worker never waited");
+                thread::sleep(Duration::from_millis(1));
+            }
+            let report = ioc.lock_report();
+            let (_, ref waiter) = report.waiters[0];
+            assert_eq!(waiter.thread_name.as_deref(), Some("worker"));
+            assert_eq!(waiter.access, Access::Read);
+            assert_eq!(waiter.label.as_deref(), Some("worker"));
+
+            drop(guard);
+            acquired_rx.recv().unwrap();
+            let report = ioc.lock_report();
+            assert!(report.waiters.is_empty());
+            assert_eq!(report.holders.len(), 1);
+            assert_eq!(report.holders[0].1.thread_name.as_deref(), Some("worker"));
+            release_tx.send(()).unwrap();
+        });
+        let report = ioc.lock_report();
+        assert!(report.holders.is_empty() && report.waiters.is_empty());
+    }
+
+    struct App;
+    service!(App, "app", Read<Repo>);
+    struct Repo;
+    service!(Repo, "repo", Read<Db>);
+    struct Db;
+    service!(Db, "db");
+
+    /// `Db` fails to be constructed.
+    fn lazy_chain() -> Container<&'static str, dyn Base> {
+        let mut builder = Builder::new();
+        builder.register_lazy::<App, Read<Repo>, Failed>(|_| Ok(App));
+        builder.register_lazy::<Repo, Read<Db>, Failed>(|_| Ok(Repo));
+        builder.register_lazy::<Db, (), Failed>(|()| Err(Failed));
+        builder.build()
+    }
+
+    #[test]
+    fn errors_display_the_resolution_path() {
+        let ioc = lazy_chain();
+        let err = ioc.read::<App>().err().unwrap();
+        let expected = r#"["db"] Failed to create service or object: failed (while resolving "app" -> "repo" -> "db")."#;
+        assert_eq!(err.to_string(), expected);
+        assert_eq!(err.into_owned().to_string(), expected);
+    }
+
+    #[test]
+    fn owned_errors_keep_path_and_source() {
+        fn is_send_sync<T: Send + Sync + 'static>(_: &T) {}
+
+        let ioc = lazy_chain();
+        let err = thread::scope(|s| s.spawn(|| ioc.read::<App>().err().unwrap().into_owned()).join().unwrap());
+        is_send_sync(&err);
+        assert_eq!(err.path(), [&"app", &"repo", &"db"]);
+        assert!(matches!(*err.root(), OwnedError::CreationError{ key: "db", .. }));
+        assert!(err.source().unwrap().is::<Failed>());
+    }
+
+    #[test]
+    fn factory_errors_are_nested_below_the_factory() {
+        // The factory itself
+        let ioc = Builder::new().build();
+        let err = ioc.resolve::<Create<Obj>>().err().unwrap();
+        assert!(matches!(err, Error::NotFound{ key: &"z" }));
+
+        // one of its arguments
+        let mut builder = Builder::new();
+        builder.register(F).register(Slow);
+        let ioc = builder.build();
+        let err = ioc.resolve::<Create<Obj>>().err().unwrap();
+        assert!(matches!(*err.root(), Error::NotFound{ key: &"x" }));
+        assert_eq!(err.path(), [&"z", &"x"]);
+
+        // and a dependency of a lazy argument
+        let mut builder = Builder::new();
+        builder.register(F).register(X);
+        builder.register_lazy::<Slow, Read<Db>, Failed>(|_| Ok(Slow));
+        builder.register_lazy::<Db, (), Failed>(|()| Err(Failed));
+        let ioc = builder.build();
+        let err = ioc.resolve::<Create<Obj>>().err().unwrap();
+        assert_eq!(err.to_string(), r#"["db"] Failed to create service or object: failed (while resolving "z" -> "s" -> "db")."#);
+    }
+
+    struct Faulty;
+    service!(Faulty, "faulty");
+    struct Part;
+
+    impl FactoryObject for Part {
+        type Factory = Faulty;
+    }
+
+    impl<'a> Factory<'a, &'static str, dyn Base, Part> for Faulty {
+        type Args = ();
+        type Error = Failed;
+
+        fn create(&self, (): ()) -> Result<Part, Failed> {
+            Err(Failed)
+        }
+    }
+
+    #[test]
+    fn create_uses_the_factory() {
+        let mut builder = Builder::new();
+        builder.register(F).register(Slow).register(X).register(Faulty);
+        let ioc = builder.build();
+        assert!(ioc.resolve::<Create<Obj>>().is_ok());
+
+        let err = ioc.resolve::<Create<Part>>().err().unwrap();
+        assert!(matches!(err, Error::CreationError{ key: &"faulty", .. }));
+        assert!(err.source().unwrap().is::<Failed>());
+    }
+
+    struct Ticket(usize);
+    service!(Ticket, "ticket");
+
+    #[test]
+    fn make_constructs_without_locking() {
+        let issued = Arc::new(AtomicUsize::new(0));
+        let counter = issued.clone();
+        let mut builder = Builder::new();
+        builder.register_transient::<Ticket, (), Failed>(move |()| Ok(Ticket(counter.fetch_add(1, Ordering::Relaxed))));
+        builder.collect_stats();
+        let ioc = builder.build();
+
+        assert_eq!(ioc.resolve::<Make<Ticket>>().unwrap().0, 0);
+        let (first, second) = ioc.try_resolve::<Make<(Ticket, Ticket)>>().unwrap();
+        assert_eq!((first.0, second.0), (1, 2));
+        assert_eq!(issued.load(Ordering::Relaxed), 3);
+        assert_eq!(ioc.stats().services[&"ticket"], LockStats::default());
+    }
+
+    struct Visit {
+        id: usize,
+        live: Arc<AtomicUsize>,
+    }
+    service!(Visit, "visit");
+
+    impl Drop for Visit {
+        fn drop(&mut self) {
+            self.live.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn scopes_own_their_scoped_instances() {
+        let live = Arc::new(AtomicUsize::new(0));
+        let (next, counter) = (AtomicUsize::new(0), live.clone());
+        let mut builder = Builder::new();
+        builder.register_scoped::<Visit, (), Failed>(move |()| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(Visit{ id: next.fetch_add(1, Ordering::Relaxed), live: counter.clone() })
+        });
+        let ioc = builder.build();
+
+        let (first, second) = (ioc.scope(), ioc.scope());
+        assert_eq!(first.read::<Visit>().unwrap().id, 0);
+        assert_eq!(second.read::<Visit>().unwrap().id, 1);
+        assert_eq!(first.read::<Visit>().unwrap().id, 0);
+        assert!(matches!(ioc.read::<Visit>(), Err(Error::MismatchedLifetime{ .. })));
+        assert!(ioc.get_service(&"visit").is_none());
+
+        assert_eq!(live.load(Ordering::Relaxed), 2);
+        drop(first);
+        assert_eq!(live.load(Ordering::Relaxed), 1);
+        drop(second);
+        assert_eq!(live.load(Ordering::Relaxed), 0);
+    }
+
+    struct Impostor;
+    service!(Impostor, "a");
+
+    #[test]
+    fn mismatched_type_reports_the_registered_type() {
+        let mut builder = Builder::new();
+        builder.register(A);
+        builder.register_lazy::<B, (), Failed>(|()| Ok(B));
+        let ioc = builder.build();
+
+        match ioc.read::<Impostor>() {
+            Err(Error::MismatchedType{ key, expected, found }) => {
+                assert_eq!((*key, expected, found), ("a", type_name::<Impostor>(), type_name::<A>()));
+            }
+            _ => panic!("expected a mismatched type"),
+        }
+        match ioc.write_service::<A>(&"b") {
+            Err(Error::MismatchedType{ found, .. }) => assert_eq!(found, type_name::<B>()),
+            _ => panic!("expected a mismatched type"),
+        };
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn take(&self) -> Vec<String> {
+            mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl Observer<&'static str> for Recorder {
+        fn resolve_started(&self, deps: &[Dependency<&'static str>]) {
+            let keys: Vec<_> = deps.iter().map(|dep| *dep.key).collect();
+            self.0.lock().unwrap().push(format!("started {:?}", keys));
+        }
+
+        fn lock_acquired(&self, key: &&'static str, access: Access, _: Duration) {
+            self.0.lock().unwrap().push(format!("acquired {:?} {}", access, key));
+        }
+
+        fn guard_released(&self, key: &&'static str, access: Access, _: Duration) {
+            self.0.lock().unwrap().push(format!("released {:?} {}", access, key));
+        }
+
+        fn resolve_failed(&self, error: &Error<&'static str>, _: Duration) {
+            self.0.lock().unwrap().push(format!("failed {}", error.key()));
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_resolve() {
+        let recorder = Recorder::default();
+        let mut builder = Builder::new();
+        builder.register(A).register(B).register(Name("observed"));
+        builder.register_lazy::<Greeting, Read<Name>, Failed>(|name| Ok(Greeting(name.0.to_owned())));
+        builder.observe(recorder.clone());
+        let ioc = builder.build();
+
+        drop(ioc.resolve::<(Read<A>, Write<B>)>().unwrap());
+        assert_eq!(recorder.take(), [
+            r#"started ["a", "b"]"#, "acquired Read a", "acquired Write b", "released Read a", "released Write b",
+        ]);
+
+        assert!(ioc.resolve::<(Read<A>, Read<X>)>().is_err());
+        assert_eq!(recorder.take(), [r#"started ["a", "x"]"#, "failed x"]);
+
+        drop(ioc.resolve::<Read<Greeting>>().unwrap());
+        assert_eq!(recorder.take(), [
+            r#"started ["greeting"]"#, 
+            r#"started ["name"]"#, "acquired Read name", "released Read name", 
+            "acquired Read greeting", "released Read greeting",
+        ]);
+    }
+
+    /// Records the names of spans when they are created and closed, and the messages 
+    /// of events.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct Capture {
+        log: Arc<Mutex<Vec<String>>>,
+        spans: Mutex<Vec<&'static str>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    struct Message(String);
+
+    #[cfg(feature = "tracing")]
+    impl ::tracing::field::Visit for Message {
+        fn record_debug(&mut self, field: &::tracing::field::Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl ::tracing::Subscriber for Capture {
+        fn enabled(&self, _: &::tracing::Metadata) -> bool { true }
+
+        fn new_span(&self, span: &::tracing::span::Attributes) -> ::tracing::Id {
+            let name = span.metadata().name();
+            self.log.lock().unwrap().push(format!("new {}", name));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(name);
+            ::tracing::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &::tracing::Id, _: &::tracing::span::Record) {}
+
+        fn record_follows_from(&self, _: &::tracing::Id, _: &::tracing::Id) {}
+
+        fn event(&self, event: &::tracing::Event) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.log.lock().unwrap().push(message.0);
+        }
+
+        fn enter(&self, _: &::tracing::Id) {}
+
+        fn exit(&self, _: &::tracing::Id) {}
+
+        fn try_close(&self, id: ::tracing::Id) -> bool {
+            let name = self.spans.lock().unwrap()[id.into_u64() as usize - 1];
+            self.log.lock().unwrap().push(format!("close {}", name));
+            true
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn resolves_and_guards_are_traced() {
+        let mut builder = Builder::new();
+        builder.register(A).register(X);
+        let ioc = builder.build();
+
+        let capture = Capture::default();
+        let log = capture.log.clone();
+        ::tracing::subscriber::with_default(capture, || {
+            let guard = ioc.resolve::<Read<A>>().unwrap();
+            assert_eq!(*log.lock().unwrap(), ["new resolve", "lock acquired", "new guard", "close resolve"]);
+            drop(guard);
+            assert_eq!(log.lock().unwrap().last().unwrap(), "close guard");
+
+            log.lock().unwrap().clear();
+            assert!(ioc.resolve::<Read<B>>().is_err());
+            assert_eq!(*log.lock().unwrap(), ["new resolve", "resolve failed", "close resolve"]);
+        });
+    }
+
+    struct Ping;
+    service!(Ping, "ping", Read<Pong>);
+    struct Pong;
+    service!(Pong, "pong", (Read<Ping>, Read<X>));
+
+    #[test]
+    fn try_build_reports_missing_dependencies_and_cycles() {
+        let mut builder = Builder::new();
+        builder.register(Ping).register(Pong);
+        let err = builder.try_build().err().unwrap();
+        assert_eq!(err.missing, [("pong", "x")]);
+        assert_eq!(err.cycles, [["ping", "pong", "ping"]]);
+    }
+
+    struct Name(&'static str);
+    service!(Name, "name");
+    struct Greeting(String);
+    service!(Greeting, "greeting", Read<Name>);
+    struct Session(String);
+    service!(Session, "session", Read<Greeting>);
+
+    #[test]
+    fn overrides_renew_dependent_services() {
+        let mut builder = Builder::new();
+        builder.register(Name("real"));
+        builder.register_lazy::<Greeting, Read<Name>, DummyError>(|name| Ok(Greeting(format!("hello {}", name.0))));
+        builder.register_scoped::<Session, Read<Greeting>, DummyError>(|greeting| Ok(Session(greeting.0.clone())));
+        builder.register_lazy::<A, (), DummyError>(|()| Ok(A));
+        let ioc = builder.build();
+        assert_eq!(ioc.read::<Greeting>().unwrap().0, "hello real");
+        assert!(ioc.read::<A>().is_ok());
+
+        let view = ioc.with_overrides(|b| { b.register(Name("fake")); });
+        assert_eq!(view.read::<Greeting>().unwrap().0, "hello fake");
+        assert_eq!(view.scope().read::<Session>().unwrap().0, "hello fake");
+        assert!(matches!(view.read::<Session>(), Err(Error::MismatchedLifetime{ .. })));
+        assert_eq!(ioc.read::<Greeting>().unwrap().0, "hello real");
+        assert_eq!(ioc.scope().read::<Session>().unwrap().0, "hello real");
+
+        // Services which don't depend on an override are shared.
+        let shared = view.get_service(&"a").unwrap();
+        assert!(::std::ptr::eq(shared, ioc.get_service(&"a").unwrap()));
+    }
 }
@@ -0,0 +1,96 @@
+use methods::Access;
+use reflect;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::Location;
+use std::thread::{self, ThreadId};
+use std::time::Instant;
+
+thread_local! {
+    /// See `label_locks`.
+    static LABEL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// ++++++++++++++++++++ LockReport ++++++++++++++++++++
+
+/// Labels the guards acquired by the current thread until the returned value is
+/// dropped, see `Container::lock_report`.
+pub fn label_locks(label: impl Into<String>) -> LockLabel {
+    let prev = LABEL.with(|cur| cur.borrow_mut().replace(label.into()));
+    LockLabel{ prev }
+}
+
+/// Restores the previous label when dropped, see `label_locks`.
+pub struct LockLabel {
+    prev: Option<String>,
+}
+
+impl Drop for LockLabel {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        LABEL.with(|cur| *cur.borrow_mut() = prev);
+    }
+}
+
+/// A thread holding or waiting for a guard, see `Container::lock_report`.
+#[derive(Clone, Debug)]
+pub struct LockHolder {
+    pub thread: ThreadId,
+    pub thread_name: Option<String>,
+    pub access: Access,
+    /// When the guard has been acquired, or waiting for it started.
+    pub since: Instant,
+    /// Where the container has been called, as far as it is known.
+    pub location: Option<&'static Location<'static>>,
+    /// See `label_locks`.
+    pub label: Option<String>,
+}
+
+impl LockHolder {
+    /// The current thread, waiting for a guard since now.
+    pub(crate) fn current(access: Access, location: Option<&'static Location<'static>>) -> Self {
+        let thread = thread::current();
+        LockHolder{
+            thread: thread.id(),
+            thread_name: thread.name().map(str::to_owned),
+            access,
+            since: Instant::now(),
+            location,
+            label: LABEL.with(|label| label.borrow().clone()),
+        }
+    }
+}
+
+/// The guards of a container which are currently held or waited for.
+#[derive(Clone, Debug)]
+pub struct LockReport<Key> {
+    pub holders: Vec<(Key, LockHolder)>,
+    pub waiters: Vec<(Key, LockHolder)>,
+}
+
+impl<Key> fmt::Display for LockReport<Key>
+    where Key: reflect::Key
+{
+    /// One line per holder and waiter, e.g.
+    /// `thread worker-7 holds Write<"cache"> since 30.0s at src/main.rs:10:5`.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (verb, list) in &[("holds", &self.holders), ("waits for", &self.waiters)] {
+            for (key, holder) in list.iter() {
+                match holder.thread_name {
+                    Some(ref name) => write!(fmt, "thread {}", name)?,
+                    None => write!(fmt, "thread {:?}", holder.thread)?,
+                }
+                write!(fmt, " {} {:?}<{:?}> since {:.1?}", verb, holder.access, key, holder.since.elapsed())?;
+                if let Some(location) = holder.location {
+                    write!(fmt, " at {}", location)?;
+                }
+                if let Some(ref label) = holder.label {
+                    write!(fmt, " ({})", label)?;
+                }
+                writeln!(fmt)?;
+            }
+        }
+        Ok(())
+    }
+}
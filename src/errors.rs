@@ -1,8 +1,11 @@
+use container::Lifetime;
+use lifecycle::Phase;
 use reflect;
 
 use std::error::Error as StdError;
 use std::fmt::{self, Display};
 use std::sync::{PoisonError, TryLockError};
+use std::time::Duration;
 
 // ++++++++++++++++++++ DummyError ++++++++++++++++++++
 
@@ -21,32 +24,133 @@ impl StdError for DummyError {
 // ++++++++++++++++++++ Error ++++++++++++++++++++
 // TODO: error messages need some work
 
+const CREATION_ERROR: &str = "Failed to create service or object";
+
 #[derive(Debug)]
 pub enum Error<'a, Key: 'a> {
     NotFound{ key: &'a Key },
     Poisoned{ key: &'a Key },
     WouldBlock{ key: &'a Key },
+    Timeout{ key: &'a Key, waited: Duration },
+    ConflictingAccess{ key: &'a Key },
     MismatchedType{ key: &'a Key, expected: &'static str, found: &'static str },
-    CreationError{ key: &'a Key, error: Box<StdError> }
+    MismatchedLifetime{ key: &'a Key, expected: Lifetime, found: Lifetime },
+    /// Constructing the service `key` requires the service itself.
+    Cycle{ key: &'a Key },
+    CreationError{ key: &'a Key, error: Box<dyn StdError + Send + Sync> },
+    /// `error` occured while resolving the dependencies of the service `key`.
+    Resolving{ key: &'a Key, error: Box<Error<'a, Key>> }
 }
 
 impl<'a, Key> Display for Error<'a, Key>
     where Key: reflect::Key
 {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        let desc = self.description();
+        self.root().fmt_root(fmt)?;
+        fmt_path(fmt, &self.path())?;
+        fmt.write_str(".")
+    }
+}
+
+impl<'a, Key> Error<'a, Key> {
+    /// The key of the service which caused the error, see `root`.
+    pub fn key(&self) -> &'a Key {
+        match *self {
+            Error::NotFound{ key } 
+            | Error::Poisoned{ key } 
+            | Error::WouldBlock{ key } 
+            | Error::Timeout{ key, .. } 
+            | Error::ConflictingAccess{ key } 
+            | Error::MismatchedType{ key, .. } 
+            | Error::MismatchedLifetime{ key, .. } 
+            | Error::Cycle{ key } 
+            | Error::CreationError{ key, .. } => key,
+            Error::Resolving{ ref error, .. } => error.key(),
+        }
+    }
+
+    /// The error which caused this one, with all `Resolving` layers stripped.
+    pub fn root(&self) -> &Self {
+        match *self {
+            Error::Resolving{ ref error, .. } => error.root(),
+            _ => self,
+        }
+    }
+
+    /// The keys being resolved when the error occured, outermost first and ending 
+    /// with the key of the root error.
+    pub fn path(&self) -> Vec<&'a Key> {
+        let mut path = Vec::new();
+        let mut err = self;
+        while let Error::Resolving{ key, ref error } = *err {
+            path.push(key);
+            err = error;
+        }
+        path.push(err.key());
+        path
+    }
+
+    /// Wraps this error, recording that it occured while resolving the service `key`.
+    pub fn resolving(self, key: &'a Key) -> Self {
+        Error::Resolving{ key, error: Box::new(self) }
+    }
+
+    /// Applies `f` to the root error, keeping the resolution-path.
+    pub fn map_root(self, f: impl FnOnce(Self) -> Self) -> Self {
         match self {
-            &Error::NotFound{ key } 
-            | &Error::Poisoned{ key } 
-            | &Error::WouldBlock{ key } => {
-                fmt.write_fmt(format_args!("[{:?}] {}.", key, desc))
+            Error::Resolving{ key, error } => Error::Resolving{ key, error: Box::new(error.map_root(f)) },
+            err => f(err),
+        }
+    }
+
+    /// Converts this error into an `OwnedError` by cloning the keys.
+    pub fn into_owned(self) -> OwnedError<Key>
+        where Key: reflect::Key
+    {
+        OwnedError::from(self)
+    }
+
+    fn message(&self) -> &'static str {
+        match *self {
+            Error::NotFound{ .. } => "Service could not be found",
+            Error::Poisoned{ .. } => "Service could not be aquired, mutex was poisoned",
+            Error::WouldBlock{ .. } => "Service could not be aquired, mutex would block",
+            Error::Timeout{ .. } => "Service could not be aquired, timed out",
+            Error::ConflictingAccess{ .. } => "Service is locked more than once, at least once for writing",
+            Error::MismatchedType{ .. } => "Service is of wrong type",
+            Error::MismatchedLifetime{ .. } => "Service has wrong lifetime",
+            Error::Cycle{ .. } => "Service depends on itself while being constructed",
+            Error::CreationError{ .. } => CREATION_ERROR,
+            Error::Resolving{ ref error, .. } => error.message(),
+        }
+    }
+
+    /// Formats a non-`Resolving` error without the trailing period.
+    fn fmt_root(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> 
+        where Key: reflect::Key
+    {
+        let desc = self.message();
+        match *self {
+            Error::NotFound{ key } 
+            | Error::Poisoned{ key } 
+            | Error::WouldBlock{ key } 
+            | Error::ConflictingAccess{ key } 
+            | Error::Cycle{ key } => {
+                fmt.write_fmt(format_args!("[{:?}] {}", key, desc))
             }
-            &Error::MismatchedType{ key, expected, found } => {
-                fmt.write_fmt(format_args!("[{:?}] {}: Expected '{}' found '{}'.", key, desc, expected, found))
+            Error::Timeout{ key, waited } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: Waited {:?}", key, desc, waited))
             }
-            &Error::CreationError{ key, ref error } => {
-                fmt.write_fmt(format_args!("[{:?}] {}: {}.", key, desc, error))
+            Error::MismatchedType{ key, expected, found } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: Expected '{}' found '{}'", key, desc, expected, found))
             }
+            Error::MismatchedLifetime{ key, expected, found } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: Expected {:?} found {:?}", key, desc, expected, found))
+            }
+            Error::CreationError{ key, ref error } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: {}", key, desc, error))
+            }
+            Error::Resolving{ ref error, .. } => error.fmt_root(fmt),
         }
     }
 }
@@ -55,12 +159,13 @@ impl<'a, Key> StdError for Error<'a, Key>
     where Key: reflect::Key
 {
     fn description(&self) -> &str {
-        match self {
-            &Error::NotFound{ .. } => "Service could not be found",
-            &Error::Poisoned{ .. } => "Service could not be aquired, mutex was poisoned",
-            &Error::WouldBlock{ .. } => "Service could not be aquired, mutex would block",
-            &Error::MismatchedType{ .. } => "Service is of wrong type",
-            &Error::CreationError{ .. } => "Factory failed to create object",
+        self.message()
+    }
+
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self.root() {
+            Error::CreationError{ ref error, .. } => Some(&**error),
+            _ => None,
         }
     }
 }
@@ -69,7 +174,7 @@ impl<'a, Key, X> From<(&'a Key, PoisonError<X>)> for Error<'a, Key>
     where Key: reflect::Key
 {
     fn from((key, _): (&'a Key, PoisonError<X>)) -> Self {
-        Error::Poisoned{ key: key }
+        Error::Poisoned{ key }
     }
 }
 
@@ -78,19 +183,324 @@ impl<'a, Key, X> From<(&'a Key, TryLockError<X>)> for Error<'a, Key>
 {
     fn from((key, err): (&'a Key, TryLockError<X>)) -> Self {
         match err {
-            TryLockError::Poisoned(_) => Error::Poisoned{ key: key },
-            TryLockError::WouldBlock => Error::WouldBlock{ key: key }
+            TryLockError::Poisoned(_) => Error::Poisoned{ key },
+            TryLockError::WouldBlock => Error::WouldBlock{ key }
+        }
+    }
+}
+
+// ++++++++++++++++++++ OwnedError ++++++++++++++++++++
+
+/// Owned counterpart of `Error`, holding a clone of the key.
+///
+/// Unlike `Error` it is `Send + Sync + 'static`, so it can be returned from threads,
+/// stored or converted into application error types via `?`.
+#[derive(Debug)]
+pub enum OwnedError<Key> {
+    NotFound{ key: Key },
+    Poisoned{ key: Key },
+    WouldBlock{ key: Key },
+    Timeout{ key: Key, waited: Duration },
+    ConflictingAccess{ key: Key },
+    MismatchedType{ key: Key, expected: &'static str, found: &'static str },
+    MismatchedLifetime{ key: Key, expected: Lifetime, found: Lifetime },
+    Cycle{ key: Key },
+    CreationError{ key: Key, error: Box<dyn StdError + Send + Sync> },
+    Resolving{ key: Key, error: Box<OwnedError<Key>> }
+}
+
+impl<Key> OwnedError<Key> {
+    /// The key of the service which caused the error, see `root`.
+    pub fn key(&self) -> &Key {
+        match *self {
+            OwnedError::NotFound{ ref key } 
+            | OwnedError::Poisoned{ ref key } 
+            | OwnedError::WouldBlock{ ref key } 
+            | OwnedError::Timeout{ ref key, .. } 
+            | OwnedError::ConflictingAccess{ ref key } 
+            | OwnedError::MismatchedType{ ref key, .. } 
+            | OwnedError::MismatchedLifetime{ ref key, .. } 
+            | OwnedError::Cycle{ ref key } 
+            | OwnedError::CreationError{ ref key, .. } => key,
+            OwnedError::Resolving{ ref error, .. } => error.key(),
+        }
+    }
+
+    /// The error which caused this one, with all `Resolving` layers stripped.
+    pub fn root(&self) -> &Self {
+        match *self {
+            OwnedError::Resolving{ ref error, .. } => error.root(),
+            _ => self,
+        }
+    }
+
+    /// See `Error::path`.
+    pub fn path(&self) -> Vec<&Key> {
+        let mut path = Vec::new();
+        let mut err = self;
+        while let OwnedError::Resolving{ ref key, ref error } = *err {
+            path.push(key);
+            err = error;
+        }
+        path.push(err.key());
+        path
+    }
+
+    /// Borrows a root error as an `Error`, `None` for `CreationError` whose source 
+    /// can't be shared and for `Resolving`.
+    fn as_error(&self) -> Option<Error<'_, Key>> {
+        Some(match *self {
+            OwnedError::NotFound{ ref key } => Error::NotFound{ key },
+            OwnedError::Poisoned{ ref key } => Error::Poisoned{ key },
+            OwnedError::WouldBlock{ ref key } => Error::WouldBlock{ key },
+            OwnedError::Timeout{ ref key, waited } => Error::Timeout{ key, waited },
+            OwnedError::ConflictingAccess{ ref key } => Error::ConflictingAccess{ key },
+            OwnedError::MismatchedType{ ref key, expected, found } => {
+                Error::MismatchedType{ key, expected, found }
+            }
+            OwnedError::MismatchedLifetime{ ref key, expected, found } => {
+                Error::MismatchedLifetime{ key, expected, found }
+            }
+            OwnedError::Cycle{ ref key } => Error::Cycle{ key },
+            OwnedError::CreationError{ .. } | OwnedError::Resolving{ .. } => return None,
+        })
+    }
+}
+
+impl<Key> Display for OwnedError<Key>
+    where Key: reflect::Key
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self.root() {
+            OwnedError::CreationError{ ref key, ref error } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: {}", key, CREATION_ERROR, error))?
+            }
+            ref root => root.as_error().expect("root error").fmt_root(fmt)?,
+        }
+        fmt_path(fmt, &self.path())?;
+        fmt.write_str(".")
+    }
+}
+
+impl<Key> StdError for OwnedError<Key>
+    where Key: reflect::Key
+{
+    fn description(&self) -> &str {
+        self.root().as_error().map_or(CREATION_ERROR, |err| err.message())
+    }
+
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self.root() {
+            OwnedError::CreationError{ ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl<'a, Key> From<Error<'a, Key>> for OwnedError<Key>
+    where Key: reflect::Key
+{
+    fn from(err: Error<'a, Key>) -> Self {
+        match err {
+            Error::NotFound{ key } => OwnedError::NotFound{ key: key.clone() },
+            Error::Poisoned{ key } => OwnedError::Poisoned{ key: key.clone() },
+            Error::WouldBlock{ key } => OwnedError::WouldBlock{ key: key.clone() },
+            Error::Timeout{ key, waited } => OwnedError::Timeout{ key: key.clone(), waited },
+            Error::ConflictingAccess{ key } => OwnedError::ConflictingAccess{ key: key.clone() },
+            Error::MismatchedType{ key, expected, found } => {
+                OwnedError::MismatchedType{ key: key.clone(), expected, found }
+            }
+            Error::MismatchedLifetime{ key, expected, found } => {
+                OwnedError::MismatchedLifetime{ key: key.clone(), expected, found }
+            }
+            Error::Cycle{ key } => OwnedError::Cycle{ key: key.clone() },
+            Error::CreationError{ key, error } => {
+                OwnedError::CreationError{ key: key.clone(), error }
+            }
+            Error::Resolving{ key, error } => {
+                OwnedError::Resolving{ key: key.clone(), error: Box::new(OwnedError::from(*error)) }
+            }
+        }
+    }
+}
+
+// ++++++++++++++++++++ BuildError ++++++++++++++++++++
+
+/// Misconfigurations found by `ContainerBuilder::try_build`.
+#[derive(Debug)]
+pub struct BuildError<Key> {
+    /// `(service, dependency)` pairs, where `dependency` is not registered.
+    pub missing: Vec<(Key, Key)>,
+    /// Dependency cycles, each starting and ending with the same key.
+    pub cycles: Vec<Vec<Key>>,
+}
+
+impl<Key> Display for BuildError<Key>
+    where Key: reflect::Key
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt.write_str("Container is misconfigured:")?;
+        let mut sep = " ";
+        for (key, dep) in &self.missing {
+            fmt.write_fmt(format_args!("{}[{:?}] depends on unregistered {:?}", sep, key, dep))?;
+            sep = "; ";
+        }
+        for cycle in &self.cycles {
+            fmt.write_fmt(format_args!("{}dependency cycle ", sep))?;
+            fmt_chain(fmt, cycle)?;
+            sep = "; ";
+        }
+        fmt.write_str(".")
+    }
+}
+
+impl<Key> StdError for BuildError<Key>
+    where Key: reflect::Key
+{
+    fn description(&self) -> &str {
+        "Container is misconfigured"
+    }
+}
+
+// ++++++++++++++++++++ LifecycleError ++++++++++++++++++++
+
+/// Failed hooks of `Container::start_all` or `Container::stop_all`.
+#[derive(Debug)]
+pub struct LifecycleError<Key> {
+    /// The failed service, the hook and its error.
+    pub failures: Vec<(Key, Phase, Box<dyn StdError + Send + Sync>)>,
+}
+
+impl<Key> Display for LifecycleError<Key>
+    where Key: reflect::Key
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt.write_str("Lifecycle hooks failed:")?;
+        let mut sep = " ";
+        for (key, phase, error) in &self.failures {
+            fmt.write_fmt(format_args!("{}[{:?}] {:?}: {}", sep, key, phase, error))?;
+            sep = "; ";
+        }
+        fmt.write_str(".")
+    }
+}
+
+impl<Key> StdError for LifecycleError<Key>
+    where Key: reflect::Key
+{
+    fn description(&self) -> &str {
+        "Lifecycle hooks failed"
+    }
+
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.failures.first().map(|(_, _, error)| &**error as &(dyn StdError + 'static))
+    }
+}
+
+// ++++++++++++++++++++ ConfigError ++++++++++++++++++++
+
+/// Errors of `ContainerBuilder::from_config`, `key` is the entry of the config document.
+#[cfg(feature = "config")]
+#[derive(Debug)]
+pub enum ConfigError {
+    Parse{ error: Box<dyn StdError + Send + Sync> },
+    NotATable{ key: String },
+    MissingImpl{ key: String },
+    UnknownImpl{ key: String, name: String },
+    InvalidKey{ key: String, error: Box<dyn StdError + Send + Sync> },
+    InvalidParams{ key: String, name: String, error: Box<dyn StdError + Send + Sync> },
+    CreationError{ key: String, name: String, error: Box<dyn StdError + Send + Sync> },
+}
+
+#[cfg(feature = "config")]
+impl Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let desc = self.message();
+        match *self {
+            ConfigError::Parse{ ref error } => {
+                fmt.write_fmt(format_args!("{}: {}.", desc, error))
+            }
+            ConfigError::NotATable{ ref key } | ConfigError::MissingImpl{ ref key } => {
+                fmt.write_fmt(format_args!("[{:?}] {}.", key, desc))
+            }
+            ConfigError::UnknownImpl{ ref key, ref name } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: '{}'.", key, desc, name))
+            }
+            ConfigError::InvalidKey{ ref key, ref error } => {
+                fmt.write_fmt(format_args!("[{:?}] {}: {}.", key, desc, error))
+            }
+            ConfigError::InvalidParams{ ref key, ref name, ref error } 
+            | ConfigError::CreationError{ ref key, ref name, ref error } => {
+                fmt.write_fmt(format_args!("[{:?}] {} '{}': {}.", key, desc, name, error))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl ConfigError {
+    fn message(&self) -> &'static str {
+        match *self {
+            ConfigError::Parse{ .. } => "Config document could not be parsed",
+            ConfigError::NotATable{ .. } => "Config entry is not a table",
+            ConfigError::MissingImpl{ .. } => "Config entry has no `impl` field naming the implementation",
+            ConfigError::UnknownImpl{ .. } => "Unknown implementation",
+            ConfigError::InvalidKey{ .. } => "Config entry is no valid service key",
+            ConfigError::InvalidParams{ .. } => "Invalid parameters for implementation",
+            ConfigError::CreationError{ .. } => "Failed to create implementation",
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl StdError for ConfigError {
+    fn description(&self) -> &str {
+        self.message()
+    }
+
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            ConfigError::Parse{ ref error } 
+            | ConfigError::InvalidKey{ ref error, .. } 
+            | ConfigError::InvalidParams{ ref error, .. } 
+            | ConfigError::CreationError{ ref error, .. } => Some(&**error),
+            _ => None,
         }
     }
 }
 
 // ++++++++++++++++++++ utility ++++++++++++++++++++
 
+/// Writes ` (while resolving a -> b -> c)` for nested errors.
+fn fmt_path<Key>(fmt: &mut fmt::Formatter, path: &[&Key]) -> Result<(), fmt::Error>
+    where Key: reflect::Key
+{
+    if path.len() < 2 {
+        return Ok(());
+    }
+    fmt.write_str(" (while resolving ")?;
+    fmt_chain(fmt, path.iter().cloned())?;
+    fmt.write_str(")")
+}
+
+/// Writes `a -> b -> c`.
+fn fmt_chain<'k, Key>(fmt: &mut fmt::Formatter, keys: impl IntoIterator<Item = &'k Key>) -> Result<(), fmt::Error>
+    where Key: reflect::Key
+{
+    for (idx, key) in keys.into_iter().enumerate() {
+        if idx > 0 {
+            fmt.write_str(" -> ")?;
+        }
+        fmt.write_fmt(format_args!("{:?}", key))?;
+    }
+    Ok(())
+}
+
 /// Utility for converting `Result<X, [Poison|TryLock]Error>` to `Result<X, ioc::Error>`.
 ///
 /// Example usage:
 /// 
-/// ```
+/// ```ignore
 /// fn foo<'a>(bar: &'a RwLock<Svc>) -> Result<Foo, ioc::Error<'a, Key>> {
 ///     // doesn't work due to ioc::Error requiring the service key
 ///     let foo = try!{lock.read()}.do_something();
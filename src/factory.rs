@@ -1,84 +1,58 @@
-use error::Error;
-use methods::Method;
-use container::Container;
+use errors::Error;
+use methods::{Method, Read};
+use container::{Acquire, Container};
 use reflect;
 
+use downcast::Downcast;
+
 use std::any::Any;
 use std::error::Error as StdError;
-use std::ops::Deref;
 
-pub trait FactoryBase<'a, Key, SvcBase: ?Sized, Obj>: Any 
+/// The part of a factory `ioc::Create` calls into.
+///
+/// Implemented automatically for every `Factory` which is a service.
+pub trait FactoryBase<'a, Key, SvcBase: ?Sized, Obj>: Any
     where Key: reflect::Key, SvcBase: Any
 {
-    fn create(
-        &self, 
-        self_key: &'a Key, 
-        ioc: &'a Container<Key, SvcBase>
-    ) -> Result<Obj, Error<'a, Key>>;
+    /// Resolves the factory together with its `Args` and creates an object.
+    ///
+    /// The read-guard of the factory is acquired in key-order along with the locks of 
+    /// the arguments, like any other dependency of a method.
+    fn create(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Obj, Error<'a, Key>>;
 }
 
-pub trait Factory<'a, Cont, Obj>: Any
-    where Cont: Container<'a>
+pub trait Factory<'a, Key, SvcBase: ?Sized, Obj>: Any
+    where Key: reflect::Key, SvcBase: Any
 {
-    type Args: Method<'a, Cont>;
-    type ArgsRet = <Self::Args as Method<'a, Cont>>::Ret;
-
-    type Error: StdError;
-
-    fn create(&self, args: <Self::Args as Method<'a, Cont>>::Ret) -> Result<Obj, Self::Error>;
-}
+    /// The services which get resolved and passed to `create`.
+    type Args: Method<'a, Key, SvcBase>;
 
-/// Auto-implement `FactoryBase` for a type which implements `Factory`.
-/// 
-/// FIXME: Remove (and replace) this as soon as partial-impls are available.
-#[macro_export]
-macro_rules! qioc_autoimpl_factory_base {
-    ($ty:ty, $cont:ty, $obj:ty) => {
-        impl<'a> ::qioc::FactoryBase<'a, $cont, $obj> for $ty {
-            fn create(
-                &self, 
-                self_key: &'a <$cont as ::qioc::Container<'a>>::Key, 
-                ioc: &'a $cont
-            ) -> Result<$obj, ::qioc::Error<'a, <$cont as ::ioc::Container<'a>>::Key>> {
-                // TODO use try!
-                let args = <<Self as ::qioc::Factory<'a, $cont, $obj>>::ArgSelection as ::qioc::Method<_>>::invoke(ioc).unwrap();
+    type Error: StdError + Send + Sync + 'static;
 
-                match ::qioc::Factory::create(self, args) {
-                    Ok(r) => Ok(r),
-                    Err(err) => Err(::qioc::Error::CreationError{ 
-                        key: self_key,
-                        error: Box::new(err)
-                    })
-                }
-            }
-        }
-   
-    };
+    fn create(&self, args: <Self::Args as Method<'a, Key, SvcBase>>::Ret) -> Result<Obj, Self::Error>;
 }
 
-// ++++++++++++++++++++ newtype-impls ++++++++++++++++++++
-
-impl<'a, Cont, Obj, T> FactoryBase<'a, Cont, Obj> for T
-    where Cont: Container<'a>, T: Any + Deref, T::Target: FactoryBase<'a, Cont, Obj>
+fn creation_error<'a, Key, Obj, E>(key: &'a Key, res: Result<Obj, E>) -> Result<Obj, Error<'a, Key>>
+    where E: StdError + Send + Sync + 'static
 {
-    fn create(
-        &self, 
-        self_key: &'a Cont::Key,
-        ioc: &'a Cont
-    ) -> Result<Obj, Error<'a, Cont::Key>> {
-        (**self).create(self_key, ioc)
-    }
+    res.map_err(|err| Error::CreationError{ key, error: Box::new(err) })
 }
 
-impl<'a, Cont, Obj, T> Factory<'a, Cont, Obj> for T
-    where Cont: Container<'a>, T: Any + Deref, T::Target: Factory<'a, Cont, Obj>
+impl<'a, Key, SvcBase: ?Sized, Obj, T> FactoryBase<'a, Key, SvcBase, Obj> for T
+where 
+    Key: reflect::Key, 
+    SvcBase: Downcast<T>, 
+    T: Factory<'a, Key, SvcBase, Obj> + reflect::Service<Key = Key>,
 {
-    type ArgSelection = <T::Target as Factory<'a, Cont, Obj>>::ArgSelection;
-
-    type Error = <T::Target as Factory<'a, Cont, Obj>>::Error;
-
-    fn create(&self, args: <Self::ArgSelection as Method<'a, Cont>>::Ret) -> Result<Obj, Self::Error> {
-        (**self).create(args)
+    fn create(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Obj, Error<'a, Key>> {
+        let self_key = T::key();
+        // Errors of the factory itself didn't occur while resolving its arguments.
+        let (factory, args) = ioc.resolve_with::<(Read<T>, T::Args)>(acquire)
+            .map_err(|err| match err {
+                Error::Resolving{ .. } => err.resolving(self_key),
+                _ if err.key() == self_key => err,
+                _ => err.resolving(self_key),
+            })?;
+        creation_error(self_key, Factory::create(&*factory, args))
     }
 }
-
@@ -0,0 +1,179 @@
+use container::Lifetime;
+use methods::Access;
+use reflect;
+
+use std::fmt::Write;
+
+// ++++++++++++++++++++ DependencyGraph ++++++++++++++++++++
+
+/// A registered service, see `Container::dependency_graph`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node<Key> {
+    pub key: Key,
+    /// Name of the concrete service type, as far as it is known.
+    pub type_name: &'static str,
+    pub lifetime: Lifetime,
+}
+
+/// A declared dependency of the service `from` on the service `to`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge<Key> {
+    pub from: Key,
+    pub to: Key,
+    pub access: Access,
+}
+
+/// The declared dependencies between the services of a container.
+///
+/// Edges may point to keys without a node if a dependency is not registered, see
+/// `ContainerBuilder::try_build`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyGraph<Key> {
+    pub nodes: Vec<Node<Key>>,
+    pub edges: Vec<Edge<Key>>,
+}
+
+impl<Key> DependencyGraph<Key>
+    where Key: reflect::Key
+{
+    /// Renders the graph in Graphviz DOT format.
+    ///
+    /// Nodes are identified by the `Debug` representation of their key, write
+    /// dependencies are drawn bold and creating ones dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph ioc {\n");
+        for node in &self.nodes {
+            let key = format!("{:?}", node.key);
+            let label = format!("{}\n{}", key, node.type_name);
+            let _ = writeln!(out, "    {} [label={}];", quoted(&key), quoted(&label));
+        }
+        for edge in &self.edges {
+            let style = match edge.access {
+                Access::Read => "solid",
+                Access::Write => "bold",
+                Access::Create => "dashed",
+            };
+            let _ = writeln!(
+                out,
+                "    {} -> {} [style={}];",
+                quoted(&format!("{:?}", edge.from)),
+                quoted(&format!("{:?}", edge.to)),
+                style
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the graph as JSON object with `nodes` and `edges` arrays.
+    ///
+    /// Keys are written as the string of their `Debug` representation.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"key\":{},\"type\":{},\"lifetime\":\"{:?}\"}}",
+                json_str(&format!("{:?}", node.key)),
+                json_str(node.type_name),
+                node.lifetime
+            );
+        }
+        out.push_str("],\"edges\":[");
+        for (idx, edge) in self.edges.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"from\":{},\"to\":{},\"access\":\"{:?}\"}}",
+                json_str(&format!("{:?}", edge.from)),
+                json_str(&format!("{:?}", edge.to)),
+                edge.access
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+// ++++++++++++++++++++ utility ++++++++++++++++++++
+
+/// Quotes `s` as DOT ID, which is also how the Prometheus text format quotes label 
+/// values.
+pub(crate) fn quoted(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(type_name: &'static str) -> DependencyGraph<&'static str> {
+        DependencyGraph{
+            nodes: vec![
+                Node{ key: "a", type_name, lifetime: Lifetime::Singleton },
+                Node{ key: "b\n", type_name: "B", lifetime: Lifetime::Scoped },
+            ],
+            edges: vec![
+                Edge{ from: "a", to: "b\n", access: Access::Write },
+                Edge{ from: "b\n", to: "c", access: Access::Create },
+            ],
+        }
+    }
+
+    #[test]
+    fn to_dot_escapes_ids() {
+        assert_eq!(graph("A<\"x\">").to_dot(), concat!(
+            "digraph ioc {\n",
+            r#"    "\"a\"" [label="\"a\"\nA<\"x\">"];"#, "\n",
+            r#"    "\"b\\n\"" [label="\"b\\n\"\nB"];"#, "\n",
+            r#"    "\"a\"" -> "\"b\\n\"" [style=bold];"#, "\n",
+            r#"    "\"b\\n\"" -> "\"c\"" [style=dashed];"#, "\n",
+            "}\n",
+        ));
+    }
+
+    #[test]
+    fn to_json_escapes_strings() {
+        assert_eq!(graph("A<\"x\">\t\u{1}").to_json(), concat!(
+            r#"{"nodes":["#,
+            r#"{"key":"\"a\"","type":"A<\"x\">\t\u0001","lifetime":"Singleton"},"#,
+            r#"{"key":"\"b\\n\"","type":"B","lifetime":"Scoped"}"#,
+            r#"],"edges":["#,
+            r#"{"from":"\"a\"","to":"\"b\\n\"","access":"Write"},"#,
+            r#"{"from":"\"b\\n\"","to":"\"c\"","access":"Create"}"#,
+            "]}",
+        ));
+    }
+}
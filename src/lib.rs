@@ -1,17 +1,46 @@
-#[macro_use] 
+#[cfg_attr(test, macro_use)]
 extern crate downcast;
+#[cfg(feature = "config")]
+extern crate serde;
+#[cfg(feature = "config")]
+extern crate serde_json;
+#[cfg(feature = "config")]
+extern crate toml;
+#[cfg(feature = "tracing")]
+#[macro_use]
+extern crate tracing;
+
+#[cfg(test)]
+#[macro_use]
+mod testing;
 
 mod reflect;
 mod errors;
-//mod factory;
+mod factory;
 mod methods;
 mod container;
+mod graph;
+mod lifecycle;
+mod stats;
+mod observer;
+mod diagnostics;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "registry")]
+pub mod registry;
 
 pub use reflect::*;
 pub use errors::*;
 pub use methods::*;
-//pub use factory::*;
+pub use factory::*;
 pub use container::*;
+pub use graph::*;
+pub use lifecycle::*;
+pub use stats::*;
+pub use observer::*;
+pub use diagnostics::*;
+#[cfg(feature = "config")]
+pub use config::*;
 
 // NOTE old code
 // TODO move this to tests/examples
@@ -0,0 +1,36 @@
+use std::error::Error as StdError;
+
+/// The hooks of `Lifecycle`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Init,
+    Start,
+    Stop,
+}
+
+/// Hooks called by `Container::start_all` and `Container::stop_all`, enabled per
+/// service with `ContainerBuilder::lifecycle`.
+///
+/// The hooks of a service run after those of its declared dependencies, `stop`
+/// runs before, see `reflect::Service::dependencies`.
+pub trait Lifecycle {
+    type Error: StdError + Send + Sync + 'static;
+
+    /// Called by `start_all` before any service is started.
+    fn init(&mut self) -> Result<(), Self::Error> { Ok(()) }
+
+    /// Called by `start_all` once all services have been initialized.
+    fn start(&mut self) -> Result<(), Self::Error> { Ok(()) }
+
+    /// Called by `stop_all`.
+    fn stop(&mut self) -> Result<(), Self::Error> { Ok(()) }
+}
+
+/// Teardown hook called when the container is dropped, enabled per service with
+/// `ContainerBuilder::dispose`.
+///
+/// Services are disposed and dropped before their declared dependencies, services
+/// without dependencies between them in reverse registration order.
+pub trait Dispose {
+    fn dispose(&mut self);
+}
@@ -1,6 +1,6 @@
 use errors::Error;
-//use factory::FactoryBase;
-use container::{ReadGuard, WriteGuard, Container};
+use factory::FactoryBase;
+use container::{Acquire, Guards, ReadGuard, WriteGuard, Container};
 use reflect;
 
 use downcast::Downcast;
@@ -8,27 +8,81 @@ use downcast::Downcast;
 use std::marker::PhantomData;
 use std::any::Any;
 
+// ++++++++++++++++++++ Dependencies ++++++++++++++++++++
+
+/// How a method accesses a service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Access {
+    /// A read-guard is held.
+    Read,
+    /// A write-guard is held.
+    Write,
+    /// A new object is created (`Create`, `Make`), no guard is held afterwards.
+    Create,
+}
+
+impl Access {
+    /// Whether a guard of the service is held by the resolved method.
+    pub fn is_lock(self) -> bool {
+        self != Access::Create
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Dependency<Key: 'static> {
+    pub key: &'static Key,
+    pub access: Access,
+}
+
+impl<Key> Clone for Dependency<Key> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<Key> Copy for Dependency<Key> {}
+
+/// The services a method accesses, independent of the container it's resolved from.
+pub trait Dependencies<Key>: Any
+    where Key: reflect::Key
+{
+    /// Appends the accessed services to `out`, in the order they appear in the method.
+    fn dependencies(out: &mut Vec<Dependency<Key>>);
+}
+
 // ++++++++++++++++++++ Method ++++++++++++++++++++
 
-pub trait Method<'a, Key, SvcBase: ?Sized>: Any
+/// A selection of services which can be resolved from a container.
+///
+/// Resolution happens in two steps: First `prepare` creates all new objects (`Create`,
+/// `Make`), then the container acquires the locks of all `Access::Read`/`Access::Write`
+/// dependencies in key-order and passes them to `finish` in declaration-order.
+pub trait Method<'a, Key, SvcBase: ?Sized>: Dependencies<Key>
     where Key: reflect::Key, SvcBase: Any
 {
     type Ret: 'a;
-    fn resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>>;
-    fn try_resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>>;
+    /// Objects created by `prepare`.
+    type Prepared: 'a;
+    fn prepare(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Self::Prepared, Error<'a, Key>>;
+    fn finish(prep: Self::Prepared, guards: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>>;
 }
 
 macro_rules! impl_nil {
     ($nil_ty:ty) => {
+        impl<Key> Dependencies<Key> for $nil_ty
+            where Key: reflect::Key
+        {
+            fn dependencies(_: &mut Vec<Dependency<Key>>) {}
+        }
+
         impl<'a, Key, SvcBase: ?Sized> Method<'a, Key, SvcBase> for $nil_ty
             where Key: reflect::Key, SvcBase: Any
         {
             type Ret = ();
+            type Prepared = ();
 
-            fn resolve_unprotected(_: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+            fn prepare(_: &'a Container<Key, SvcBase>, _: Acquire) -> Result<(), Error<'a, Key>> {
                 Ok(())
             }
-            fn try_resolve_unprotected(_: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+            fn finish(_: (), _: &mut Guards<'a, Key, SvcBase>) -> Result<(), Error<'a, Key>> {
                 Ok(())
             }
         }
@@ -43,6 +97,16 @@ pub struct Read<Svc>(PhantomData<fn(Svc)>);
 
 impl_nil!(Read<()>);
 
+impl<Key, Svc> Dependencies<Key> for Read<Svc>
+where
+    Key: reflect::Key,
+    Svc: reflect::Service<Key = Key>,
+{
+    fn dependencies(out: &mut Vec<Dependency<Key>>) {
+        out.push(Dependency{ key: Svc::key(), access: Access::Read });
+    }
+}
+
 impl<'a, Key, SvcBase: ?Sized, Svc> Method<'a, Key, SvcBase> for Read<Svc>
 where 
     Key: reflect::Key,
@@ -50,16 +114,27 @@ where
     SvcBase: Downcast<Svc>,
 {
     type Ret = ReadGuard<'a, Svc, SvcBase>;
-    fn resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
-        ioc.read::<Svc>()
+    type Prepared = ();
+    fn prepare(_: &'a Container<Key, SvcBase>, _: Acquire) -> Result<(), Error<'a, Key>> {
+        Ok(())
     }
-    fn try_resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
-        ioc.try_read::<Svc>()
+    fn finish(_: (), guards: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+        guards.read::<Svc>()
     }
 }
 
 macro_rules! multi_read {
     ($({$($params:ident)+})+) => {$(
+        impl<Key, $($params),+> Dependencies<Key> for Read<($($params,)+)>
+        where
+            Key: reflect::Key,
+            $($params: reflect::Service<Key = Key>),+
+        {
+            fn dependencies(out: &mut Vec<Dependency<Key>>) {
+                $(Read::<$params>::dependencies(out);)+
+            }
+        }
+
         impl<'a, Key, SvcBase: ?Sized, $($params),+> Method<'a, Key, SvcBase> for Read<($($params,)+)>
         where
             Key: reflect::Key,
@@ -67,14 +142,13 @@ macro_rules! multi_read {
             $(SvcBase: Downcast<$params>),+
         {
             type Ret = ($(<Read<$params> as Method<'a, Key, SvcBase>>::Ret,)+);
-            fn resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
-                Ok((
-                    $(try!{ioc.read::<$params>()},)+
-                ))
+            type Prepared = ();
+            fn prepare(_: &'a Container<Key, SvcBase>, _: Acquire) -> Result<(), Error<'a, Key>> {
+                Ok(())
             }
-            fn try_resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+            fn finish(_: (), guards: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
                 Ok((
-                    $(try!{ioc.try_read::<$params>()},)+
+                    $(guards.read::<$params>()?,)+
                 ))
             }
         }
@@ -106,6 +180,16 @@ pub struct Write<Svc>(PhantomData<fn(Svc)>);
 
 impl_nil!(Write<()>);
 
+impl<Key, Svc> Dependencies<Key> for Write<Svc>
+where
+    Key: reflect::Key,
+    Svc: reflect::Service<Key = Key>,
+{
+    fn dependencies(out: &mut Vec<Dependency<Key>>) {
+        out.push(Dependency{ key: Svc::key(), access: Access::Write });
+    }
+}
+
 impl<'a, Key, SvcBase: ?Sized, Svc> Method<'a, Key, SvcBase> for Write<Svc>
 where 
     Key: reflect::Key,
@@ -113,16 +197,27 @@ where
     SvcBase: Downcast<Svc>,
 {
     type Ret = WriteGuard<'a, Svc, SvcBase>;
-    fn resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
-        ioc.write::<Svc>()
+    type Prepared = ();
+    fn prepare(_: &'a Container<Key, SvcBase>, _: Acquire) -> Result<(), Error<'a, Key>> {
+        Ok(())
     }
-    fn try_resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
-        ioc.try_write::<Svc>()
+    fn finish(_: (), guards: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+        guards.write::<Svc>()
     }
 }
 
 macro_rules! multi_write {
     ($({$($params:ident)+})+) => {$(
+        impl<Key, $($params),+> Dependencies<Key> for Write<($($params,)+)>
+        where
+            Key: reflect::Key,
+            $($params: reflect::Service<Key = Key>),+
+        {
+            fn dependencies(out: &mut Vec<Dependency<Key>>) {
+                $(Write::<$params>::dependencies(out);)+
+            }
+        }
+
         impl<'a, Key, SvcBase: ?Sized, $($params),+> Method<'a, Key, SvcBase> for Write<($($params,)+)>
         where
             Key: reflect::Key,
@@ -130,14 +225,13 @@ macro_rules! multi_write {
             $(SvcBase: Downcast<$params>),+
         {
             type Ret = ($(<Write<$params> as Method<'a, Key, SvcBase>>::Ret,)+);
-            fn resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
-                Ok((
-                    $(try!{ioc.write::<$params>()},)+
-                ))
+            type Prepared = ();
+            fn prepare(_: &'a Container<Key, SvcBase>, _: Acquire) -> Result<(), Error<'a, Key>> {
+                Ok(())
             }
-            fn try_resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+            fn finish(_: (), guards: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
                 Ok((
-                    $(try!{ioc.try_write::<$params>()},)+
+                    $(guards.write::<$params>()?,)+
                 ))
             }
         }
@@ -163,43 +257,182 @@ multi_write!{
     {A B C D E F G H J K L M N O P Q}
 }
 
-/*
 // ++++++++++++++++++++ Create ++++++++++++++++++++
 
-pub struct Create<Obj>(fn(Obj));
+pub struct Create<Obj>(PhantomData<fn(Obj)>);
+
+impl_nil!(Create<()>);
 
-impl<'a, Cont, Obj> Method<'a, Cont> for Create<Obj>
+impl<Key, Obj> Dependencies<Key> for Create<Obj>
+where
+    Key: reflect::Key,
+    Obj: reflect::FactoryObject,
+    Obj::Factory: reflect::Service<Key = Key>,
+{
+    fn dependencies(out: &mut Vec<Dependency<Key>>) {
+        out.push(Dependency{ key: <Obj::Factory as reflect::Service>::key(), access: Access::Create });
+    }
+}
+
+impl<'a, Key, SvcBase: ?Sized, Obj> Method<'a, Key, SvcBase> for Create<Obj>
 where 
-    Obj: reflect::FactoryObject<Key = Cont::Key>,
-    Obj::Factory: FactoryBase<'a, Cont, Obj>,
-    Cont: Container<'a>,
-    Cont::ServiceBase: Downcast<Obj::Factory>,
+    Key: reflect::Key,
+    Obj: reflect::FactoryObject,
+    Obj::Factory: reflect::Service<Key = Key> + FactoryBase<'a, Key, SvcBase, Obj>,
+    SvcBase: Downcast<Obj::Factory>,
 {
     type Ret = Obj;
-    fn resolve_unprotected(ioc: &'a Cont) -> Result<Self::Ret, Error<'a, Cont::Key>> {
-        ioc.create::<Obj>()
+    type Prepared = Obj;
+    fn prepare(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Obj, Error<'a, Key>> {
+        match acquire {
+            Acquire::Block => ioc.create::<Obj>(),
+            Acquire::Try => ioc.try_create::<Obj>(),
+        }
+    }
+    fn finish(obj: Obj, _: &mut Guards<'a, Key, SvcBase>) -> Result<Obj, Error<'a, Key>> {
+        Ok(obj)
     }
 }
 
 macro_rules! multi_create {
     ($({$($params:ident)+})+) => {$(
-        impl<'a, Cont, $($params),+> Method<'a, Cont> for Create<($($params,)+)>
+        impl<Key, $($params),+> Dependencies<Key> for Create<($($params,)+)>
+        where
+            Key: reflect::Key,
+            $($params: reflect::FactoryObject),+,
+            $($params::Factory: reflect::Service<Key = Key>),+
+        {
+            fn dependencies(out: &mut Vec<Dependency<Key>>) {
+                $(Create::<$params>::dependencies(out);)+
+            }
+        }
+
+        impl<'a, Key, SvcBase: ?Sized, $($params),+> Method<'a, Key, SvcBase> for Create<($($params,)+)>
+        where
+            Key: reflect::Key,
+            $($params: reflect::FactoryObject),+,
+            $($params::Factory: reflect::Service<Key = Key> + FactoryBase<'a, Key, SvcBase, $params>),+,
+            $(SvcBase: Downcast<$params::Factory>),+
+        {
+            type Ret = ($(<Create<$params> as Method<'a, Key, SvcBase>>::Ret,)+);
+            type Prepared = Self::Ret;
+            fn prepare(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Self::Ret, Error<'a, Key>> {
+                Ok((
+                    $(Create::<$params>::prepare(ioc, acquire)?,)+
+                ))
+            }
+            fn finish(objs: Self::Ret, _: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+                Ok(objs)
+            }
+        }
+    )+}
+}
+
+multi_create!{
+    {A} 
+    {A B} 
+    {A B C}
+    {A B C D}
+    {A B C D E}
+    {A B C D E F}
+    {A B C D E F G}
+    {A B C D E F G H}
+    {A B C D E F G H J}
+    {A B C D E F G H J K}
+    {A B C D E F G H J K L}
+    {A B C D E F G H J K L M}
+    {A B C D E F G H J K L M N}
+    {A B C D E F G H J K L M N O}
+    {A B C D E F G H J K L M N O P}
+    {A B C D E F G H J K L M N O P Q}
+}
+
+// ++++++++++++++++++++ Make ++++++++++++++++++++
+
+pub struct Make<Svc>(PhantomData<fn(Svc)>);
+
+impl_nil!(Make<()>);
+
+impl<Key, Svc> Dependencies<Key> for Make<Svc>
+where
+    Key: reflect::Key,
+    Svc: reflect::Service<Key = Key>,
+{
+    fn dependencies(out: &mut Vec<Dependency<Key>>) {
+        out.push(Dependency{ key: Svc::key(), access: Access::Create });
+    }
+}
+
+impl<'a, Key, SvcBase: ?Sized, Svc> Method<'a, Key, SvcBase> for Make<Svc>
+where 
+    Key: reflect::Key,
+    Svc: reflect::Service<Key = Key>,
+    SvcBase: Downcast<Svc>,
+{
+    type Ret = Svc;
+    type Prepared = Svc;
+    fn prepare(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Svc, Error<'a, Key>> {
+        match acquire {
+            Acquire::Block => ioc.make::<Svc>(),
+            Acquire::Try => ioc.try_make::<Svc>(),
+        }
+    }
+    fn finish(svc: Svc, _: &mut Guards<'a, Key, SvcBase>) -> Result<Svc, Error<'a, Key>> {
+        Ok(svc)
+    }
+}
+
+macro_rules! multi_make {
+    ($({$($params:ident)+})+) => {$(
+        impl<Key, $($params),+> Dependencies<Key> for Make<($($params,)+)>
         where
-            $($params: reflect::FactoryObject<Key = Cont::Key>),+,
-            $($params::Factory: FactoryBase<'a, Cont, $params>),+,
-            Cont: Container<'a>,
-            $(Cont::ServiceBase: Downcast<$params::Factory>),+
+            Key: reflect::Key,
+            $($params: reflect::Service<Key = Key>),+
         {
-            type Ret = ($(<Create<$params> as Method<'a, Cont>>::Ret,)+);
-            fn resolve_unprotected(ioc: &'a Cont) -> Result<Self::Ret, Error<'a, Cont::Key>> {
+            fn dependencies(out: &mut Vec<Dependency<Key>>) {
+                $(Make::<$params>::dependencies(out);)+
+            }
+        }
+
+        impl<'a, Key, SvcBase: ?Sized, $($params),+> Method<'a, Key, SvcBase> for Make<($($params,)+)>
+        where
+            Key: reflect::Key,
+            $($params: reflect::Service<Key = Key>),+,
+            $(SvcBase: Downcast<$params>),+
+        {
+            type Ret = ($(<Make<$params> as Method<'a, Key, SvcBase>>::Ret,)+);
+            type Prepared = Self::Ret;
+            fn prepare(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Self::Ret, Error<'a, Key>> {
                 Ok((
-                    $(try!{ioc.create::<$params>()},)+
+                    $(Make::<$params>::prepare(ioc, acquire)?,)+
                 ))
             }
+            fn finish(svcs: Self::Ret, _: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+                Ok(svcs)
+            }
         }
     )+}
 }
-*/
+
+multi_make!{
+    {A} 
+    {A B} 
+    {A B C}
+    {A B C D}
+    {A B C D E}
+    {A B C D E F}
+    {A B C D E F G}
+    {A B C D E F G H}
+    {A B C D E F G H J}
+    {A B C D E F G H J K}
+    {A B C D E F G H J K L}
+    {A B C D E F G H J K L M}
+    {A B C D E F G H J K L M N}
+    {A B C D E F G H J K L M N O}
+    {A B C D E F G H J K L M N O P}
+    {A B C D E F G H J K L M N O P Q}
+}
+
 // ++++++++++++++++++++ multi-method ++++++++++++++++++++
 
 macro_rules! e {
@@ -209,6 +442,16 @@ macro_rules! e {
 macro_rules! multi_methods {
     ($({$($idx:tt,$params:ident)+})+) => {$(
         
+        impl<Key, $($params),+> Dependencies<Key> for ($($params,)+)
+        where
+            Key: reflect::Key,
+            $($params: Dependencies<Key>),+
+        {
+            fn dependencies(out: &mut Vec<Dependency<Key>>) {
+                $($params::dependencies(out);)+
+            }
+        }
+
         impl<'a, Key, SvcBase: ?Sized, $($params),+> Method<'a, Key, SvcBase> for ($($params,)+) 
         where 
             Key: reflect::Key,
@@ -216,14 +459,15 @@ macro_rules! multi_methods {
             SvcBase: Any,
         {
             type Ret = ($($params::Ret,)+);
-            fn resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+            type Prepared = ($($params::Prepared,)+);
+            fn prepare(ioc: &'a Container<Key, SvcBase>, acquire: Acquire) -> Result<Self::Prepared, Error<'a, Key>> {
                 Ok((
-                    $(try!{e![$params::resolve_unprotected(ioc)]},)+
+                    $($params::prepare(ioc, acquire)?,)+
                 ))
             }
-            fn try_resolve_unprotected(ioc: &'a Container<Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
+            fn finish(prep: Self::Prepared, guards: &mut Guards<'a, Key, SvcBase>) -> Result<Self::Ret, Error<'a, Key>> {
                 Ok((
-                    $(try!{e![$params::try_resolve_unprotected(ioc)]},)+
+                    $($params::finish(e![prep.$idx], guards)?,)+
                 ))
             }
         }
@@ -249,7 +493,3 @@ multi_methods!{
     {0,A 1,B 2,C 3,D 4,E 5,F 6,G 7,H 8,J 9,K 10,L 11,M 12,N 13,O 14,P}
     {0,A 1,B 2,C 3,D 4,E 5,F 6,G 7,H 8,J 9,K 10,L 11,M 12,N 13,O 14,P 15,Q}
 }
-
-
-
-
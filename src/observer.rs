@@ -0,0 +1,29 @@
+use errors::Error;
+use methods::{Access, Dependency};
+use reflect;
+
+use std::time::Duration;
+
+/// Hooks into the resolution of methods, installed with `ContainerBuilder::observe`.
+///
+/// Hooks are called on the resolving thread, possibly while guards are held, so
+/// they shouldn't resolve services themselves. All of them do nothing by default.
+pub trait Observer<Key>: Send + Sync
+    where Key: reflect::Key
+{
+    /// Called when `Container::resolve` or one of its variants starts resolving a
+    /// method with the dependencies `deps`.
+    fn resolve_started(&self, _deps: &[Dependency<Key>]) {}
+
+    /// Called when a guard of `key` has been acquired after waiting for `waited`.
+    ///
+    /// This includes guards acquired outside of `resolve`, e.g. by `Container::read`.
+    fn lock_acquired(&self, _key: &Key, _access: Access, _waited: Duration) {}
+
+    /// Called when a guard of `key` has been released after being held for `held`.
+    fn guard_released(&self, _key: &Key, _access: Access, _held: Duration) {}
+
+    /// Called when resolving a method failed after `elapsed`, `error.key()` is the
+    /// key of the service which caused it.
+    fn resolve_failed(&self, _error: &Error<Key>, _elapsed: Duration) {}
+}
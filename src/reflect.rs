@@ -1,3 +1,5 @@
+use methods::Dependency;
+
 use std::any::Any;
 use std::fmt::Debug;
 
@@ -10,11 +12,18 @@ impl<T> Key for T
 pub trait Service: Any + Sized {
     type Key: Key;
     fn key() -> &'static Self::Key;
+
+    /// Appends the services this service depends on to `out`, none by default.
+    ///
+    /// Usually forwards to a method describing them, e.g. 
+    /// `<(Read<Db>, Write<Cache>) as ioc::Dependencies<_>>::dependencies(out)`. The container 
+    /// only reads this as data, see `Container::dependencies`.
+    fn dependencies(_out: &mut Vec<Dependency<Self::Key>>) {}
 }
 
-/*pub trait FactoryObject: Any + Sized {
-    type Key: Key = <Self::Factory as Service>::Key;
-    type Factory: Service<Key = Self::Key> /* + FactoryBase<'a, _>*/;
-}*/
+/// An object which is created by a factory-service, see `ioc::Create`.
+pub trait FactoryObject: Any + Sized {
+    type Factory: Service;
+}
 
 
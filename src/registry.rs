@@ -0,0 +1,112 @@
+//! Service registrations submitted at link time, e.g. by plugin crates:
+//!
+//! ```ignore
+//! ioc::submit!("postgres", |builder: &mut ContainerBuilder<String, Base>| {
+//!     builder.register(Postgres::default());
+//! });
+//! ```
+//!
+//! `ContainerBuilder::register_all_from_registry` applies them.
+
+use container::ContainerBuilder;
+use reflect;
+
+use std::any::Any;
+
+#[doc(hidden)]
+pub extern crate inventory;
+
+// ++++++++++++++++++++ Submission ++++++++++++++++++++
+
+/// A named registration, see `submit!`.
+pub struct Submission {
+    pub name: &'static str,
+    /// Registers the services, given the builder as `&mut dyn Any`.
+    #[doc(hidden)]
+    pub register: fn(&mut dyn Any),
+}
+
+inventory::collect!(Submission);
+
+/// The builder as seen by `Submission::register`, named for use by `submit!`.
+#[doc(hidden)]
+pub type AnyBuilder = dyn Any;
+
+/// Returns all submissions of the program, ordered by name.
+pub fn submissions() -> Vec<&'static Submission> {
+    let mut ret: Vec<_> = inventory::iter::<Submission>.into_iter().collect();
+    ret.sort_by_key(|sub| sub.name);
+    ret
+}
+
+/// Submits the registration `name` to the global registry.
+///
+/// The closure only runs for builders of exactly the type it takes, so a program
+/// may use containers of several types side by side.
+#[macro_export]
+macro_rules! submit {
+    ($name:expr, |$builder:ident : &mut $ty:ty| $body:expr) => {
+        const _: () = {
+            fn register(builder: &mut $crate::registry::AnyBuilder) {
+                if let Some($builder) = builder.downcast_mut::<$ty>() {
+                    $body;
+                }
+            }
+            $crate::registry::inventory::submit!{
+                $crate::registry::Submission{ name: $name, register }
+            }
+        };
+    };
+}
+
+// ++++++++++++++++++++ ContainerBuilder ++++++++++++++++++++
+
+impl<Key, SvcBase: ?Sized> ContainerBuilder<Key, SvcBase>
+    where Key: reflect::Key, SvcBase: Any
+{
+    /// Applies all submissions for this type of builder, ordered by name.
+    ///
+    /// Services registered by several submissions get replaced, the last one wins.
+    pub fn register_all_from_registry(&mut self) -> &mut Self {
+        for sub in submissions() {
+            (sub.register)(self);
+        }
+        self
+    }
+}
+
+#[cfg(all(test, feature = "registry"))]
+mod tests {
+    use container::ContainerBuilder;
+    use testing::{Base, Builder};
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct Label(&'static str);
+    service!(Label, "label");
+
+    static OTHER_BUILDER: AtomicBool = AtomicBool::new(false);
+
+    submit!("registry-tests-b", |builder: &mut Builder| {
+        builder.register(Label("b"));
+    });
+    submit!("registry-tests-a", |builder: &mut Builder| {
+        builder.register(Label("a"));
+    });
+    submit!("registry-tests-other", |_builder: &mut ContainerBuilder<String, dyn Base>| {
+        OTHER_BUILDER.store(true, Ordering::Relaxed);
+    });
+
+    #[test]
+    fn submissions_apply_to_their_builder_in_name_order() {
+        let mut builder = Builder::new();
+        builder.register_all_from_registry();
+        let ioc = builder.build();
+        assert_eq!(ioc.read::<Label>().unwrap().0, "b");
+        assert!(!OTHER_BUILDER.load(Ordering::Relaxed));
+
+        let mut other = ContainerBuilder::<String, dyn Base>::new();
+        other.register_all_from_registry();
+        assert!(OTHER_BUILDER.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,76 @@
+use graph::quoted;
+use reflect;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+// ++++++++++++++++++++ ContainerStats ++++++++++++++++++++
+
+/// Lock statistics of a service, see `Container::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockStats {
+    /// Acquired read-guards.
+    pub reads: u64,
+    /// Acquired write-guards.
+    pub writes: u64,
+    /// Total time spent waiting for guards.
+    pub wait: Duration,
+    /// Total time guards have been held, guards which are still held aren't included.
+    pub hold: Duration,
+    pub max_hold: Duration,
+    /// Attempts which failed with `Error::WouldBlock`.
+    pub would_block: u64,
+    /// Attempts which failed with `Error::Poisoned`.
+    pub poisoned: u64,
+}
+
+/// The lock statistics of all services of a container.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContainerStats<Key: Ord> {
+    pub services: BTreeMap<Key, LockStats>,
+}
+
+impl<Key> ContainerStats<Key>
+    where Key: reflect::Key
+{
+    /// Renders the statistics in the Prometheus text exposition format.
+    ///
+    /// Services are labeled with the `Debug` representation of their key.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        self.metric(&mut out, "ioc_lock_acquired_total", "counter", "Acquired service guards.", |key, stats, out| {
+            let _ = writeln!(out, "ioc_lock_acquired_total{{key={},access=\"read\"}} {}", key, stats.reads);
+            let _ = writeln!(out, "ioc_lock_acquired_total{{key={},access=\"write\"}} {}", key, stats.writes);
+        });
+        self.metric(&mut out, "ioc_lock_wait_seconds_total", "counter", "Time spent waiting for service guards.", |key, stats, out| {
+            let _ = writeln!(out, "ioc_lock_wait_seconds_total{{key={}}} {}", key, stats.wait.as_secs_f64());
+        });
+        self.metric(&mut out, "ioc_lock_hold_seconds_total", "counter", "Time service guards have been held.", |key, stats, out| {
+            let _ = writeln!(out, "ioc_lock_hold_seconds_total{{key={}}} {}", key, stats.hold.as_secs_f64());
+        });
+        self.metric(&mut out, "ioc_lock_hold_seconds_max", "gauge", "Longest time a service guard has been held.", |key, stats, out| {
+            let _ = writeln!(out, "ioc_lock_hold_seconds_max{{key={}}} {}", key, stats.max_hold.as_secs_f64());
+        });
+        self.metric(&mut out, "ioc_lock_failed_total", "counter", "Failed attempts to acquire service guards.", |key, stats, out| {
+            let _ = writeln!(out, "ioc_lock_failed_total{{key={},reason=\"would_block\"}} {}", key, stats.would_block);
+            let _ = writeln!(out, "ioc_lock_failed_total{{key={},reason=\"poisoned\"}} {}", key, stats.poisoned);
+        });
+        out
+    }
+
+    fn metric(
+        &self,
+        out: &mut String,
+        name: &str,
+        kind: &str,
+        help: &str,
+        samples: impl Fn(&str, &LockStats, &mut String)
+    ) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (key, stats) in &self.services {
+            samples(&quoted(&format!("{:?}", key)), stats, out);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Service types shared by the unit tests.
+
+use container::ContainerBuilder;
+
+use downcast;
+
+pub trait Base: downcast::Any + Send + Sync {}
+
+impl<T> Base for T
+    where T: downcast::Any + Send + Sync
+{}
+
+// `impl_downcast` transmutes the trait object pointers.
+#[allow(clippy::transmute_ptr_to_ref)]
+mod base {
+    use super::Base;
+    impl_downcast!(Base);
+}
+
+pub type BoxedBase = Box<dyn Base>;
+pub type Builder = ContainerBuilder<&'static str, dyn Base>;
+
+/// Implements `reflect::Service` for `$ty`, optionally declaring the dependencies
+/// of the method `$deps`.
+macro_rules! service {
+    ($ty:ident, $key:expr) => {
+        service!($ty, $key, ());
+    };
+    ($ty:ident, $key:expr, $deps:ty) => {
+        impl ::reflect::Service for $ty {
+            type Key = &'static str;
+
+            fn key() -> &'static &'static str {
+                &$key
+            }
+
+            fn dependencies(out: &mut Vec<::methods::Dependency<&'static str>>) {
+                <$deps as ::methods::Dependencies<&'static str>>::dependencies(out)
+            }
+        }
+
+        impl From<$ty> for ::testing::BoxedBase {
+            fn from(svc: $ty) -> Self { Box::new(svc) }
+        }
+    };
+}